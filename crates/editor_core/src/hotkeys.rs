@@ -6,7 +6,7 @@ use bevy::reflect::GetTypeRegistration;
 use bevy::utils::HashMap;
 
 #[cfg(feature = "persistence_editor")]
-use space_persistence::AppPersistenceExt;
+use space_persistence::{AppPersistenceExt, USER_LAYER};
 
 pub trait Hotkey:
     Send
@@ -115,11 +115,12 @@ impl HotkeyAppExt for App {
             self.init_resource::<ButtonInput<T>>();
             #[cfg(feature = "persistence_editor")]
             {
-                self.persistence_resource_with_fn::<HotkeySet<T>>(Box::new(
-                    |dst: &mut HotkeySet<T>, src: HotkeySet<T>| {
+                self.persistence_resource_with_fn_in_layer::<HotkeySet<T>>(
+                    USER_LAYER,
+                    Box::new(|dst: &mut HotkeySet<T>, src: HotkeySet<T>| {
                         dst.bindings.extend(src.bindings);
-                    },
-                ));
+                    }),
+                );
             }
             self.add_systems(PreUpdate, hotkey_mapper::<T>);
             self.register_type::<Vec<KeyCode>>();
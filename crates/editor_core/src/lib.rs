@@ -3,24 +3,28 @@
 
 pub mod hotkeys;
 mod load;
+pub mod save_selection;
 pub mod selected;
 pub mod task_storage;
 pub mod toast;
+pub mod undo_history;
 
 pub mod prelude {
     pub use super::*;
-    pub use super::{hotkeys::*, load::*, selected::*, task_storage::*};
+    pub use super::{hotkeys::*, load::*, save_selection::*, selected::*, task_storage::*};
     pub use space_undo;
 }
 
 pub mod gltf_unpack;
 
+use std::time::Duration;
+
 use bevy::prelude::*;
 
 use prelude::load_listener;
-use space_prefab::save::{SaveConfig, SaveState};
+use space_prefab::save::{serialize_scene, SaveConfig, SaveState};
 use space_shared::*;
-use space_undo::AppAutoUndo;
+use space_undo::{AppAutoUndo, UndoCoalescing};
 use task_storage::{BackgroundTask, BackgroundTaskStorage, BackgroundTaskStoragePlugin};
 
 pub struct EditorCore;
@@ -33,6 +37,7 @@ impl Plugin for EditorCore {
         app.add_plugins(space_persistence::PersistencePlugin);
 
         app.add_plugins(BackgroundTaskStoragePlugin);
+        app.add_plugins(save_selection::SaveSelectionPlugin);
 
         app.configure_sets(Update, EditorLoadSet.in_set(EditorSet::Editor));
 
@@ -47,10 +52,21 @@ impl Plugin for EditorCore {
                 .in_set(EditorLoadSet),
         );
         app.add_systems(Update, editor_event_listener);
+        app.init_resource::<undo_history::PendingUndoHistory>();
+        app.add_systems(
+            OnEnter(SaveState::Save),
+            undo_history::store_undo_history.after(serialize_scene),
+        );
+        app.add_systems(Update, undo_history::save_undo_history);
 
         app.auto_reflected_undo::<Parent>();
         app.auto_reflected_undo::<Children>();
-        app.auto_undo::<PrefabMarker>();
+        //Reflected undo, so spawning of scene entities can be saved in persistent undo history
+        app.auto_reflected_undo::<PrefabMarker>();
+        //Gizmo drags and slider edits with short pauses must be one undo step
+        app.auto_reflected_undo_with_coalescing::<Transform>(UndoCoalescing::Window(
+            Duration::from_millis(500),
+        ));
     }
 }
 
@@ -91,6 +107,11 @@ fn editor_event_listener(
                 }
             },
             EditorEvent::Save(path) => {
+                if let EditorPrefabPath::File(file) = path {
+                    background_tasks
+                        .tasks
+                        .push(BackgroundTask::SceneSaving(file.clone()));
+                }
                 save_config.path = Some(path.clone());
                 save_state.set(SaveState::Save);
                 info!("Saving scene to {:?}", path);
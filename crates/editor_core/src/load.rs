@@ -1,19 +1,39 @@
-use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use bevy::{ecs::entity::EntityHashMap, prelude::*, scene::DynamicEntity};
+use space_prefab::save::AssetFolder;
 use space_shared::{toast::ToastMessage, *};
+use space_undo::{OneFrameUndoIgnore, UndoIgnoreResources, UndoIgnoreStorage};
 
-use crate::EditorLoader;
+use crate::{undo_history::restore_undo_history, EditorLoader};
 
 pub fn load_listener(world: &mut World) {
-    let app_registry = world.resource::<AppTypeRegistry>().clone();
     let load_server = world.resource::<EditorLoader>().clone();
     let mut prefab;
+    let scene_path;
     {
         let assets = world.resource::<Assets<DynamicScene>>();
-        if let Some(scene) = &load_server.scene {
-            if let Some(scene) = assets.get(scene) {
-                let mut scene = Scene::from_dynamic_scene(scene, &app_registry).unwrap();
-                scene.world.insert_resource(app_registry);
-                prefab = DynamicScene::from_scene(&scene); //kill me, is it clone() analog for DynamicScene
+        if let Some(handle) = &load_server.scene {
+            if let Some(scene) = assets.get(handle) {
+                //Entity ids are kept as in the scene file, persistent undo history refers to them
+                prefab = DynamicScene {
+                    resources: scene.resources.iter().map(|r| r.clone_value()).collect(),
+                    entities: scene
+                        .entities
+                        .iter()
+                        .map(|e| DynamicEntity {
+                            entity: e.entity,
+                            components: e.components.iter().map(|c| c.clone_value()).collect(),
+                        })
+                        .collect(),
+                };
+                //Scenes from memory cache have no path
+                scene_path = world
+                    .resource::<AssetServer>()
+                    .get_path(handle.id())
+                    .map(|path| {
+                        world
+                            .resource::<AssetFolder>()
+                            .file(&path.path().display().to_string())
+                    });
             } else {
                 return;
             }
@@ -29,6 +49,7 @@ pub fn load_listener(world: &mut World) {
         .map(|(e, name)| (e, name.cloned()))
         .collect();
     for (entity, name) in mark_to_delete {
+        ignore_undo_recursive(world, entity);
         let mut despawned = false;
         if let Some(e) = world.get_entity_mut(entity) {
             e.despawn_recursive();
@@ -51,6 +72,11 @@ pub fn load_listener(world: &mut World) {
         entity.components.push(Box::new(PrefabMarker));
     }
 
+    //Scene resources are restored by load, not changed by user
+    if let Some(mut ignore_resources) = world.get_resource_mut::<UndoIgnoreResources>() {
+        ignore_resources.ignore_scene(&prefab);
+    }
+
     let mut map = EntityHashMap::default();
     let res = prefab.write_to_world(world, &mut map);
     match res {
@@ -59,6 +85,9 @@ pub fn load_listener(world: &mut World) {
                 "Prefab loaded successfully",
                 egui_toast::ToastKind::Success,
             ));
+            if let Some(scene_path) = scene_path {
+                restore_undo_history(world, &scene_path, &map);
+            }
         }
         Err(err) => {
             world.send_event(ToastMessage::new(
@@ -69,3 +98,61 @@ pub fn load_listener(world: &mut World) {
         }
     }
 }
+
+/// Despawn of the old scene is not an undoable change
+fn ignore_undo_recursive(world: &mut World, entity: Entity) {
+    let mut stack = vec![entity];
+    while let Some(e) = stack.pop() {
+        if let Some(children) = world.get::<Children>(e) {
+            stack.extend(children.iter());
+        }
+        if let Some(mut ignore_storage) = world.get_resource_mut::<UndoIgnoreStorage>() {
+            ignore_storage
+                .storage
+                .insert(e, OneFrameUndoIgnore::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use space_undo::{AppAutoUndo, ChangeChain, UndoPlugin};
+
+    use super::*;
+
+    #[test]
+    fn scene_resources_are_not_undoable_on_load() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            UndoPlugin,
+        ))
+        .add_event::<ToastMessage>()
+        .init_resource::<EditorLoader>()
+        .init_resource::<AmbientLight>()
+        .register_type::<AmbientLight>()
+        .auto_undo_resource::<AmbientLight>()
+        .add_systems(Update, load_listener);
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let scene = DynamicScene {
+            resources: vec![Box::new(AmbientLight {
+                brightness: 500.0,
+                ..default()
+            })],
+            entities: vec![],
+        };
+        let handle = app.world.resource_mut::<Assets<DynamicScene>>().add(scene);
+        app.world.resource_mut::<EditorLoader>().scene = Some(handle);
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert_eq!(app.world.resource::<AmbientLight>().brightness, 500.0);
+        assert!(app.world.resource::<ChangeChain>().changes.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use space_prefab::{
+    load::PrefabBundle,
+    save::{save_entities_as_prefab, PrefabPivot, SavedSelection, SceneSaved},
+    scene_format::strip_scene_extension,
+};
+use space_shared::{
+    toast::{ToastKind, ToastMessage},
+    EditorPrefabPath, EditorSet, PrefabMarker,
+};
+use space_undo::{AddedEntity, NewChange, UndoDespawnExt, UndoTransactionExt};
+
+use crate::selected::Selected;
+
+/// Event to save selected entities with their descendants as a new prefab
+#[derive(Event, Clone, Debug)]
+pub struct SaveSelectionAsPrefab {
+    /// Path of the new prefab relative to the assets folder
+    pub path: String,
+    pub pivot: PrefabPivot,
+    /// Replace saved entities in the scene with an instance of the new prefab
+    pub replace_selection: bool,
+}
+
+pub struct SaveSelectionPlugin;
+
+impl Plugin for SaveSelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveSelectionAsPrefab>();
+        app.init_resource::<PendingSelectionSaves>();
+        app.add_systems(
+            Update,
+            (save_selection_listener, finish_selection_saves)
+                .chain()
+                .in_set(EditorSet::Editor),
+        );
+    }
+}
+
+/// Saved selections which prefab files are still being written
+#[derive(Resource, Default)]
+struct PendingSelectionSaves {
+    saves: Vec<(SaveSelectionAsPrefab, SavedSelection)>,
+}
+
+fn save_selection_listener(world: &mut World) {
+    let events: Vec<SaveSelectionAsPrefab> = world
+        .resource_mut::<Events<SaveSelectionAsPrefab>>()
+        .drain()
+        .collect();
+
+    for event in events {
+        let mut query = world.query_filtered::<Entity, With<Selected>>();
+        let selected: Vec<Entity> = query.iter(world).collect();
+
+        match save_entities_as_prefab(world, &selected, event.pivot, &event.path) {
+            Ok(saved) => {
+                world
+                    .resource_mut::<PendingSelectionSaves>()
+                    .saves
+                    .push((event, saved));
+            }
+            Err(err) => {
+                let err = format!("Failed to save selection as prefab:\n{err}");
+                world.send_event(ToastMessage::new(&err, ToastKind::Error));
+                error!(err);
+            }
+        }
+    }
+}
+
+/// Replace selection with the prefab when its file is written, so the prefab can be loaded.
+/// Failed writes are reported by the save pipeline
+fn finish_selection_saves(
+    mut commands: Commands,
+    mut events: EventReader<SceneSaved>,
+    mut pending: ResMut<PendingSelectionSaves>,
+    mut toast: EventWriter<ToastMessage>,
+) {
+    for event in events.read() {
+        let EditorPrefabPath::File(file) = &event.path else {
+            continue;
+        };
+        let Some(idx) = pending
+            .saves
+            .iter()
+            .position(|(_, saved)| saved.file == *file)
+        else {
+            continue;
+        };
+        let (request, saved) = pending.saves.remove(idx);
+        if event.result.is_err() {
+            continue;
+        }
+
+        toast.send(ToastMessage::new(
+            &format!("Saved selection to prefab {}", request.path),
+            ToastKind::Success,
+        ));
+        if request.replace_selection {
+            replace_with_instance(&mut commands, &request.path, &saved);
+        }
+    }
+}
+
+/// Despawn saved entities and spawn the new prefab at their place as one undo step
+fn replace_with_instance(commands: &mut Commands, path: &str, saved: &SavedSelection) {
+    let name = strip_scene_extension(path.rsplit('/').next().unwrap_or(path)).to_string();
+
+    commands.begin_undo_transaction(format!("Replace selection with {}", name));
+    for root in saved.roots.iter() {
+        //Entity could be removed while the file was written
+        if let Some(entity) = commands.get_entity(*root) {
+            entity.despawn_recursive_with_undo();
+        }
+    }
+    let instance = commands
+        .spawn(PrefabBundle::new(path))
+        .insert(Transform::from_translation(saved.pivot))
+        .insert((PrefabMarker, Name::new(name), Selected))
+        .id();
+    commands.add(move |world: &mut World| {
+        world.send_event(NewChange {
+            change: Arc::new(AddedEntity { entity: instance }),
+        });
+    });
+    commands.commit_undo_transaction();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use space_prefab::{
+        editor_registry::{EditorRegistryExt, EditorRegistryPlugin},
+        load::PrefabLoader,
+        save::{AssetFolder, ChildrenPrefab, SavePrefabPlugin},
+    };
+    use space_undo::UndoPlugin;
+
+    #[test]
+    fn replaces_selection_with_saved_prefab() {
+        let folder = std::env::temp_dir().join("space_editor_save_selection");
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            UndoPlugin,
+            EditorRegistryPlugin {},
+            SavePrefabPlugin,
+            SaveSelectionPlugin,
+        ))
+        .add_event::<ToastMessage>()
+        .insert_resource(AssetFolder(folder.display().to_string()))
+        .init_resource::<space_shared::PrefabMemoryCache>()
+        .register_type::<Vec<Entity>>()
+        .editor_registry::<ChildrenPrefab>()
+        .editor_registry::<Transform>()
+        .editor_registry::<Name>();
+
+        let first = app
+            .world
+            .spawn((PrefabMarker, Selected, Name::new("first")))
+            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 0.0, 2.0,
+            )))
+            .id();
+        let second = app
+            .world
+            .spawn((PrefabMarker, Selected, Name::new("second")))
+            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 0.0, 4.0,
+            )))
+            .id();
+        app.update();
+
+        app.world.send_event(SaveSelectionAsPrefab {
+            path: "test_save_selection/selection.scn.ron".to_string(),
+            pivot: PrefabPivot::Center,
+            replace_selection: true,
+        });
+        app.update();
+        //Selection is replaced only after the prefab file is written
+        assert!(app.world.get_entity(first).is_some());
+        for _ in 0..100 {
+            if app
+                .world
+                .resource::<PendingSelectionSaves>()
+                .saves
+                .is_empty()
+            {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.update();
+        }
+
+        let saved = folder
+            .join("test_save_selection/selection.scn.ron")
+            .exists();
+        let _ = std::fs::remove_dir_all(&folder);
+        assert!(saved);
+
+        assert!(app.world.get_entity(first).is_none());
+        assert!(app.world.get_entity(second).is_none());
+        let mut query = app
+            .world
+            .query_filtered::<(&PrefabLoader, &Transform), With<Selected>>();
+        let (loader, transform) = query.single(&app.world);
+        assert_eq!(loader.path, "test_save_selection/selection.scn.ron");
+        assert_eq!(transform.translation, Vec3::new(0.0, 0.0, 3.0));
+    }
+}
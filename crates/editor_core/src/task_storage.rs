@@ -1,4 +1,6 @@
 use bevy::{asset::LoadState, prelude::*};
+use space_prefab::save::SceneSaved;
+use space_shared::EditorPrefabPath;
 
 pub struct BackgroundTaskStoragePlugin;
 
@@ -7,6 +9,7 @@ impl Plugin for BackgroundTaskStoragePlugin {
         app.init_resource::<BackgroundTaskStorage>();
 
         app.add_systems(PostUpdate, update_storage);
+        app.add_systems(PostUpdate, finish_scene_saving.before(update_storage));
     }
 }
 
@@ -17,6 +20,8 @@ pub struct BackgroundTaskStorage {
 
 pub enum BackgroundTask {
     AssetLoading(String, UntypedHandle),
+    /// Scene file is being written, finished by [`SceneSaved`] event
+    SceneSaving(String),
     None,
 }
 
@@ -33,6 +38,7 @@ fn update_storage(mut storage: ResMut<BackgroundTaskStorage>, assets: Res<AssetS
                     need_remove_task = true;
                 }
             }
+            BackgroundTask::SceneSaving(_) => {}
             BackgroundTask::None => {
                 need_remove_task = true;
             }
@@ -44,6 +50,19 @@ fn update_storage(mut storage: ResMut<BackgroundTaskStorage>, assets: Res<AssetS
     }
 }
 
+fn finish_scene_saving(
+    mut storage: ResMut<BackgroundTaskStorage>,
+    mut events: EventReader<SceneSaved>,
+) {
+    for event in events.read() {
+        if let EditorPrefabPath::File(path) = &event.path {
+            storage.tasks.retain(
+                |task| !matches!(task, BackgroundTask::SceneSaving(saving) if saving == path),
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,4 +87,31 @@ mod tests {
 
         assert_eq!(app.world.resource::<BackgroundTaskStorage>().tasks.len(), 0);
     }
+
+    #[test]
+    fn scene_saving_task_waits_for_saved_event() {
+        let storage = BackgroundTaskStorage {
+            tasks: vec![BackgroundTask::SceneSaving("scene.scn.ron".to_string())],
+        };
+
+        let mut app = App::new();
+        app.insert_resource(storage)
+            .add_plugins((
+                MinimalPlugins,
+                AssetPlugin::default(),
+                ImagePlugin::default(),
+            ))
+            .add_event::<SceneSaved>()
+            .add_systems(Update, (finish_scene_saving, update_storage).chain());
+
+        app.update();
+        assert_eq!(app.world.resource::<BackgroundTaskStorage>().tasks.len(), 1);
+
+        app.world.send_event(SceneSaved {
+            path: EditorPrefabPath::File("scene.scn.ron".to_string()),
+            result: Ok(()),
+        });
+        app.update();
+        assert_eq!(app.world.resource::<BackgroundTaskStorage>().tasks.len(), 0);
+    }
 }
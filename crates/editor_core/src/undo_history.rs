@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*, utils::HashMap};
+use space_prefab::save::{SaveConfig, SavedSceneEntities, SceneSaved};
+use space_shared::{
+    toast::{ToastKind, ToastMessage},
+    EditorPrefabPath,
+};
+use space_undo::{
+    persistent_history::{self, StoredHistory},
+    ChangeChainSettings,
+};
+
+/// Undo histories of scenes which are still being written. History file is written only
+/// after its scene file, so history on disk never refers to a scene which failed to save
+#[derive(Resource, Default)]
+pub(crate) struct PendingUndoHistory {
+    histories: HashMap<String, StoredHistory>,
+}
+
+fn persistent_history_enabled(world: &World) -> bool {
+    world
+        .get_resource::<ChangeChainSettings>()
+        .is_some_and(|settings| settings.persistent_history)
+}
+
+/// Convert undo history to serializable form. Runs right after the scene was serialized,
+/// while ids of the entities in the scene file are known
+pub(crate) fn store_undo_history(world: &mut World) {
+    if !persistent_history_enabled(world) {
+        return;
+    }
+    let Some(EditorPrefabPath::File(path)) = world.resource::<SaveConfig>().path.clone() else {
+        return;
+    };
+    let saved = world.resource::<SavedSceneEntities>();
+    //Scene was not serialized
+    if saved.path != path {
+        return;
+    }
+
+    let history = persistent_history::store_history(world, &saved.entity_map);
+    world
+        .resource_mut::<PendingUndoHistory>()
+        .histories
+        .insert(path, history);
+}
+
+/// Save undo history next to the scene file when the scene file is written
+pub(crate) fn save_undo_history(
+    mut events: EventReader<SceneSaved>,
+    mut pending: ResMut<PendingUndoHistory>,
+    registry: Res<AppTypeRegistry>,
+    mut toast: EventWriter<ToastMessage>,
+) {
+    for event in events.read() {
+        let EditorPrefabPath::File(path) = &event.path else {
+            continue;
+        };
+        let Some(history) = pending.histories.remove(path) else {
+            continue;
+        };
+        if event.result.is_err() {
+            warn!("Undo history of {} is not saved, scene save failed", path);
+            continue;
+        }
+
+        let history_path = persistent_history::history_path(path);
+        match persistent_history::save_history_file(&history_path, &history, &registry.read()) {
+            Ok(_) => info!("Saved undo history to file {}", history_path),
+            Err(err) => {
+                error!("Failed to save undo history to {}: {}", history_path, err);
+                toast.send(ToastMessage::new(
+                    &format!("Failed to save undo history:\n{err}"),
+                    ToastKind::Error,
+                ));
+            }
+        }
+    }
+}
+
+/// Restore undo history of the loaded scene file if it was saved with the scene
+pub(crate) fn restore_undo_history(
+    world: &mut World,
+    scene_path: &str,
+    scene_map: &EntityHashMap<Entity>,
+) {
+    if !persistent_history_enabled(world) {
+        return;
+    }
+    let history_path = persistent_history::history_path(scene_path);
+    if !Path::new(&history_path).exists() {
+        return;
+    }
+
+    let res = persistent_history::load_history_file(world, &history_path)
+        .and_then(|history| persistent_history::restore_history(world, &history, scene_map));
+    match res {
+        Ok(_) => info!("Restored undo history from file {}", history_path),
+        Err(err) => {
+            warn!(
+                "Failed to restore undo history from {}: {}",
+                history_path, err
+            );
+            world.send_event(ToastMessage::new(
+                &format!("Undo history was not restored:\n{err}"),
+                ToastKind::Warning,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+    use space_prefab::{
+        editor_registry::{EditorRegistryExt, EditorRegistryPlugin},
+        save::{serialize_scene, SavePrefabPlugin, SaveState, SceneSerializationSettings},
+        scene_format::SceneFormat,
+    };
+    use space_shared::{PrefabMarker, PrefabMemoryCache};
+    use space_undo::{AppAutoUndo, SyncUndoMarkersPlugin, UndoPlugin, UndoRedo};
+
+    use super::*;
+
+    fn history_app(path: &str) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            UndoPlugin,
+            SyncUndoMarkersPlugin::<PrefabMarker>::default(),
+            EditorRegistryPlugin {},
+            SavePrefabPlugin,
+        ))
+        .add_event::<ToastMessage>()
+        .init_resource::<PrefabMemoryCache>()
+        .init_resource::<PendingUndoHistory>()
+        .insert_resource(SaveConfig {
+            path: Some(EditorPrefabPath::File(path.to_string())),
+        })
+        .insert_resource(ChangeChainSettings {
+            persistent_history: true,
+            ..default()
+        })
+        .add_systems(
+            OnEnter(SaveState::Save),
+            store_undo_history.after(serialize_scene),
+        )
+        .add_systems(Update, save_undo_history)
+        .editor_registry::<PrefabMarker>()
+        .editor_registry::<Name>()
+        .auto_reflected_undo::<Name>();
+        app
+    }
+
+    fn repeat_update(app: &mut App, times: usize) {
+        for _ in 0..times {
+            app.update();
+        }
+    }
+
+    fn save_scene(app: &mut App) -> SceneSaved {
+        let mut reader = ManualEventReader::<SceneSaved>::default();
+        app.world
+            .resource_mut::<NextState<SaveState>>()
+            .set(SaveState::Save);
+        for _ in 0..100 {
+            app.update();
+            let events = app.world.resource::<Events<SceneSaved>>();
+            if let Some(event) = reader.read(events).next() {
+                let event = event.clone();
+                //History is written after the event is read
+                repeat_update(app, 2);
+                return event;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("Scene was not saved");
+    }
+
+    #[test]
+    fn history_is_not_saved_when_scene_save_fails() {
+        //Scene file can not be written over a folder, while history file can
+        let path = "test_history_failed_save.scn.ron";
+        std::fs::create_dir_all(path).unwrap();
+        let mut app = history_app(path);
+        let entity = app.world.spawn((PrefabMarker, Name::new("first"))).id();
+        repeat_update(&mut app, 12);
+        app.world.get_mut::<Name>(entity).unwrap().set("second");
+        repeat_update(&mut app, 12);
+
+        let event = save_scene(&mut app);
+        let history_path = persistent_history::history_path(path);
+        let history_saved = Path::new(&history_path).exists();
+        let _ = std::fs::remove_dir(path);
+        let _ = std::fs::remove_file(&history_path);
+
+        assert!(event.result.is_err());
+        assert!(!history_saved);
+        assert!(app
+            .world
+            .resource::<PendingUndoHistory>()
+            .histories
+            .is_empty());
+    }
+
+    #[test]
+    fn undo_works_after_deterministic_save_and_reload() {
+        let path = "test_history_deterministic.scn.ron";
+        let mut app = history_app(path);
+        app.insert_resource(SceneSerializationSettings {
+            deterministic: true,
+        });
+        //Runtime ids differ from ids in the file, which are ordered by name
+        app.world.spawn_empty();
+        let renamed = app.world.spawn((PrefabMarker, Name::new("b"))).id();
+        app.world.spawn((PrefabMarker, Name::new("a")));
+        repeat_update(&mut app, 12);
+        app.world.get_mut::<Name>(renamed).unwrap().set("c");
+        repeat_update(&mut app, 12);
+
+        let event = save_scene(&mut app);
+        assert!(event.result.is_ok());
+
+        let mut app = history_app(path);
+        app.world.spawn_empty();
+        app.world.spawn_empty();
+        let history_path = persistent_history::history_path(path);
+        let bytes = std::fs::read(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        let scene = SceneFormat::from_path(path)
+            .deserialize(&bytes, &app.world.resource::<AppTypeRegistry>().read())
+            .unwrap();
+        let mut map = EntityHashMap::default();
+        scene.write_to_world(&mut app.world, &mut map).unwrap();
+        app.update();
+        restore_undo_history(&mut app.world, path, &map);
+        let _ = std::fs::remove_file(&history_path);
+
+        app.world.send_event(UndoRedo::Undo);
+        repeat_update(&mut app, 2);
+
+        let mut query = app.world.query::<&Name>();
+        let mut names: Vec<String> = query
+            .iter(&app.world)
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["a".to_string(), "b".to_string()]);
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
+use bevy_egui::egui;
 
-use super::{editor_tab::EditorTab, EditorUiAppExt};
-use space_undo::ChangeChain;
+use super::{colors::ERROR_COLOR, editor_tab::EditorTab, EditorUiAppExt};
+use space_shared::toast::{ToastKind, ToastMessage};
+use space_undo::{ChangeChain, ChangeId, UndoRedo, UndoRedoFailed};
 
 pub struct ChangeChainViewPlugin;
 
@@ -11,9 +13,20 @@ impl Plugin for ChangeChainViewPlugin {
             super::editor_tab::EditorTabName::Other("Change Chain".to_string()),
             ChangeChainView,
         );
+        app.add_systems(Update, show_undo_redo_errors);
     }
 }
 
+fn show_undo_redo_errors(
+    mut events: EventReader<UndoRedoFailed>,
+    mut toast: EventWriter<ToastMessage>,
+) {
+    for event in events.read() {
+        toast.send(ToastMessage::new(&event.error, ToastKind::Error));
+    }
+}
+
+/// Undo history panel. Shows all branches of the undo tree, click on any change jumps to it
 #[derive(Resource, Default)]
 pub struct ChangeChainView;
 
@@ -25,9 +38,30 @@ impl EditorTab for ChangeChainView {
         world: &mut bevy::prelude::World,
     ) {
         let change_chain = world.resource::<ChangeChain>();
+        let mut jump_to = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let is_current = change_chain.current().is_none();
+            if ui.selectable_label(is_current, "Initial state").clicked() && !is_current {
+                jump_to = Some(None);
+            }
+
+            draw_branch(ui, change_chain, change_chain.history_roots(), &mut jump_to);
 
-        for change in change_chain.changes.iter() {
-            ui.label(change.debug_text());
+            if let Some(transaction) = change_chain.transaction() {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} ({} changes in progress)",
+                        transaction.name,
+                        transaction.changes.len()
+                    ))
+                    .italics(),
+                );
+            }
+        });
+
+        if let Some(target) = jump_to {
+            world.send_event(UndoRedo::JumpTo(target));
         }
     }
 
@@ -35,3 +69,52 @@ impl EditorTab for ChangeChainView {
         "Change Chain".into()
     }
 }
+
+/// Draw changes which were made from the same state.
+/// Single continuation is drawn on the same level, alternative branches are indented
+fn draw_branch(
+    ui: &mut egui::Ui,
+    change_chain: &ChangeChain,
+    nodes: &[ChangeId],
+    jump_to: &mut Option<Option<ChangeId>>,
+) {
+    if let [id] = nodes {
+        draw_node(ui, change_chain, *id, jump_to);
+    } else {
+        for (idx, id) in nodes.iter().enumerate() {
+            ui.label(egui::RichText::new(format!("Branch {}", idx + 1)).weak());
+            ui.indent(id, |ui| {
+                draw_node(ui, change_chain, *id, jump_to);
+            });
+        }
+    }
+}
+
+fn draw_node(
+    ui: &mut egui::Ui,
+    change_chain: &ChangeChain,
+    id: ChangeId,
+    jump_to: &mut Option<Option<ChangeId>>,
+) {
+    let Some(node) = change_chain.history().get(&id) else {
+        return;
+    };
+
+    let is_current = change_chain.current() == Some(id);
+    let mut text = egui::RichText::new(node.change.debug_text());
+    if !change_chain.is_applied(id) {
+        text = text.weak();
+    }
+    if node.quarantined.is_some() {
+        text = text.strikethrough().color(ERROR_COLOR);
+    }
+    let mut response = ui.selectable_label(is_current, text);
+    if let Some(error) = &node.quarantined {
+        response = response.on_hover_text(format!("Skipped by undo and redo: {}", error));
+    }
+    if response.clicked() && !is_current {
+        *jump_to = Some(Some(id));
+    }
+
+    draw_branch(ui, change_chain, &node.children, jump_to);
+}
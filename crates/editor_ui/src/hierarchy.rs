@@ -7,8 +7,14 @@ use bevy_egui::{
     *,
 };
 use space_editor_core::prelude::*;
-use space_prefab::{component::SceneAutoChild, editor_registry::EditorRegistry};
-use space_undo::{AddedEntity, NewChange, RemovedEntity, UndoSet};
+use space_prefab::{
+    component::SceneAutoChild,
+    editor_registry::EditorRegistry,
+    guid::PrefabGuid,
+    load::PrefabLoader,
+    overrides::{PrefabInstanceEvent, PrefabOverrides},
+};
+use space_undo::{AddedEntity, NewChange, UndoDespawnExt, UndoSet};
 
 use space_shared::*;
 
@@ -68,6 +74,8 @@ pub fn show_hierarchy(
     mut changes: EventWriter<NewChange>,
     mut state: ResMut<HierarchyTabState>,
     auto_children: Query<(), With<SceneAutoChild>>,
+    prefab_instances: Query<Option<&PrefabOverrides>, With<PrefabLoader>>,
+    mut prefab_events: EventWriter<PrefabInstanceEvent>,
 ) {
     let mut all: Vec<_> = if state.show_editor_entities {
         all_entities.iter().collect()
@@ -111,6 +119,8 @@ pub fn show_hierarchy(
                         &mut clone_events,
                         &mut changes,
                         &auto_children,
+                        &prefab_instances,
+                        &mut prefab_events,
                     );
                 } else {
                     draw_entity::<With<PrefabMarker>>(
@@ -122,6 +132,8 @@ pub fn show_hierarchy(
                         &mut clone_events,
                         &mut changes,
                         &auto_children,
+                        &prefab_instances,
+                        &mut prefab_events,
                     );
                 }
             }
@@ -145,6 +157,8 @@ fn draw_entity<F: QueryFilter>(
     clone_events: &mut EventWriter<CloneEvent>,
     changes: &mut EventWriter<NewChange>,
     auto_children: &Query<(), With<SceneAutoChild>>,
+    prefab_instances: &Query<Option<&PrefabOverrides>, With<PrefabLoader>>,
+    prefab_events: &mut EventWriter<PrefabInstanceEvent>,
 ) {
     let Ok((_, name, children, parent)) = query.get(entity) else {
         return;
@@ -189,6 +203,8 @@ fn draw_entity<F: QueryFilter>(
                         clone_events,
                         selected,
                         parent,
+                        prefab_instances,
+                        prefab_events,
                     );
                 });
             }
@@ -221,6 +237,8 @@ fn draw_entity<F: QueryFilter>(
                     clone_events,
                     changes,
                     auto_children,
+                    prefab_instances,
+                    prefab_events,
                 );
             }
         });
@@ -251,6 +269,8 @@ fn draw_entity<F: QueryFilter>(
                     clone_events,
                     selected,
                     parent,
+                    prefab_instances,
+                    prefab_events,
                 );
             });
         }
@@ -282,6 +302,8 @@ fn hierarchy_entity_context(
     clone_events: &mut EventWriter<'_, CloneEvent>,
     selected: &mut Query<'_, '_, Entity, With<Selected>>,
     parent: Option<&Parent>,
+    prefab_instances: &Query<Option<&PrefabOverrides>, With<PrefabLoader>>,
+    prefab_events: &mut EventWriter<'_, PrefabInstanceEvent>,
 ) {
     if ui.button("Add child").clicked() {
         let new_id = commands.spawn_empty().insert(PrefabMarker).id();
@@ -292,10 +314,7 @@ fn hierarchy_entity_context(
         ui.close_menu();
     }
     if ui.button("Delete").clicked() {
-        commands.entity(entity).despawn_recursive();
-        changes.send(NewChange {
-            change: Arc::new(RemovedEntity { entity }),
-        });
+        commands.entity(entity).despawn_recursive_with_undo();
         ui.close_menu();
     }
     if ui.button("Clone").clicked() {
@@ -310,6 +329,26 @@ fn hierarchy_entity_context(
     if parent.is_some() && ui.button("Detach").clicked() {
         commands.entity(entity).remove_parent();
     }
+    if let Ok(overrides) = prefab_instances.get(entity) {
+        ui.separator();
+        if ui
+            .button("Apply to source prefab")
+            .on_hover_text("Write changes of this instance to its prefab file")
+            .clicked()
+        {
+            prefab_events.send(PrefabInstanceEvent::ApplyToSource(entity));
+            ui.close_menu();
+        }
+        let has_overrides = overrides.is_some_and(|overrides| !overrides.0.is_empty());
+        if ui
+            .add_enabled(has_overrides, egui::Button::new("Revert instance"))
+            .on_hover_text("Discard changes of this instance")
+            .clicked()
+        {
+            prefab_events.send(PrefabInstanceEvent::Revert(entity));
+            ui.close_menu();
+        }
+    }
 }
 
 #[derive(Component)]
@@ -333,6 +372,9 @@ fn clone_enitites(
                     cmds.insert(ClonedEntity);
 
                     editor_registry.clone_entity_flat(&mut cmds, &entity);
+                    if entity.contains::<PrefabGuid>() {
+                        cmds.insert(PrefabGuid::new());
+                    }
 
                     if let Some(parent) = entity.get::<Parent>() {
                         if let Some(new_parent) = map.get(&parent.get()) {
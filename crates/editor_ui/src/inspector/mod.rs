@@ -17,13 +17,15 @@ use bevy::{
 use bevy_egui::{egui::TextEdit, *};
 
 use space_editor_core::prelude::*;
-use space_prefab::{component::EntityLink, editor_registry::EditorRegistry};
+use space_prefab::{
+    component::EntityLink, editor_registry::EditorRegistry, overrides::PrefabInstanceChild,
+};
 use space_shared::ext::bevy_inspector_egui::{
     self, inspector_egui_impls::InspectorEguiImpl, reflect_inspector::InspectorUi,
 };
 
 use crate::{
-    colors::DEFAULT_BG_COLOR,
+    colors::{DEFAULT_BG_COLOR, OVERRIDE_COLOR},
     icons::add_component_icon,
     sizing::{to_label, Sizing},
 };
@@ -207,6 +209,17 @@ impl EditorTab for InspectorTab {
 
                                     let value = unsafe { reflect_from_ptr.from_ptr_mut()(ptr) };
 
+                                    let overridden = unsafe { e.get::<PrefabInstanceChild>() }
+                                        .map(|child| {
+                                            child
+                                                .overridden_fields(
+                                                    registration.type_info().type_path(),
+                                                )
+                                                .map(str::to_string)
+                                                .collect::<Vec<_>>()
+                                        })
+                                        .unwrap_or_default();
+
                                     if is_editor_component {
                                         if !editor_registry_resource
                                             .silent
@@ -219,6 +232,7 @@ impl EditorTab for InspectorTab {
                                                 &mut env,
                                                 value,
                                                 &mut set_changed,
+                                                &overridden,
                                             );
 
                                             ui.push_id(
@@ -252,6 +266,7 @@ impl EditorTab for InspectorTab {
                                             &mut env,
                                             value,
                                             &mut set_changed,
+                                            &overridden,
                                         );
                                         ui.end_row();
                                     }
@@ -371,12 +386,34 @@ impl InspectorTab {
         env: &mut InspectorUi<'_, '_>,
         value: &mut dyn Reflect,
         set_changed: &mut impl FnMut(),
+        overridden: &[String],
     ) {
         ui.push_id(format!("{:?}-{}", &e.id(), &name), |ui| {
             let default = name.to_lowercase() == *"transform";
-            let header = egui::CollapsingHeader::new(name)
+            let mut title = egui::RichText::new(name);
+            if !overridden.is_empty() {
+                title = title.color(OVERRIDE_COLOR);
+            }
+            let header = egui::CollapsingHeader::new(title)
+                .id_source(name)
                 .default_open(*self.open_components.get(name).unwrap_or(&default))
                 .show(ui, |ui| {
+                    if !overridden.is_empty() {
+                        //Fields changed in this prefab instance, they are saved as overrides
+                        let fields = overridden
+                            .iter()
+                            .map(|field| match field.trim_start_matches('.') {
+                                "" => "value",
+                                field => field,
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        ui.label(
+                            egui::RichText::new(format!("Overridden: {fields}"))
+                                .small()
+                                .color(OVERRIDE_COLOR),
+                        );
+                    }
                     ui.push_id(format!("content-{:?}-{}", &e.id(), &name), |ui| {
                         if env.ui_for_reflect_with_options(value, ui, ui.id(), &()) {
                             (set_changed)();
@@ -9,7 +9,7 @@ use space_shared::ext::bevy_inspector_egui::{
     inspector_egui_impls::InspectorEguiImpl, reflect_inspector::InspectorUi,
 };
 
-use space_prefab::component::EntityLink;
+use space_prefab::{component::EntityLink, guid::PrefabGuid};
 
 /// Method from `bevy_inspector_egui` to make dummy reflection ui
 pub fn many_unimplemented<T: Any>(
@@ -54,6 +54,7 @@ pub fn entity_ref_ui(
                             .selectable_value(&mut value.entity, e.id(), format!("{:?}", e.id()))
                             .clicked()
                         {
+                            value.guid = e.get::<PrefabGuid>().copied();
                             return true;
                         }
                     }
@@ -2,6 +2,7 @@ use bevy::{prelude::*, utils::HashMap};
 
 use bevy_egui::*;
 use space_shared::ext::bevy_inspector_egui;
+use space_undo::AutoUndoResources;
 
 use crate::prelude::*;
 
@@ -23,6 +24,14 @@ impl EditorTab for ResourceTab {
 pub fn inspect(ui: &mut egui::Ui, world: &mut World, open_resources: &mut HashMap<String, bool>) {
     let type_registry = world.resource::<AppTypeRegistry>().clone();
     let type_registry = type_registry.read();
+    let scene_resources = world
+        .get_resource::<EditorRegistry>()
+        .map(|registry| registry.scene_resources.clone())
+        .unwrap_or_default();
+    let undoable = world
+        .get_resource::<AutoUndoResources>()
+        .cloned()
+        .unwrap_or_default();
 
     let mut resources: Vec<_> = type_registry
         .iter()
@@ -38,12 +47,28 @@ pub fn inspect(ui: &mut egui::Ui, world: &mut World, open_resources: &mut HashMa
             )
         })
         .collect();
-    resources.sort_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
+    //Resources saved with scene go first
+    resources.sort_by_key(|(name, type_id)| (!scene_resources.contains(type_id), name.clone()));
+
+    //Other resources are edited in place and their changes can not be undone
+    ui.label("Only changes of undoable resources can be undone");
 
     egui::Grid::new("Resources ID".to_string()).show(ui, |ui| {
         for (resource_name, type_id) in resources {
             ui.push_id(format!("{:?}-{}", &type_id, &resource_name), |ui| {
-                let header = egui::CollapsingHeader::new(resource_name.clone())
+                let mut tags = vec![];
+                if scene_resources.contains(&type_id) {
+                    tags.push("saved with scene");
+                }
+                if undoable.contains(type_id) {
+                    tags.push("undoable");
+                }
+                let title = if tags.is_empty() {
+                    resource_name.clone()
+                } else {
+                    format!("{} ({})", resource_name, tags.join(", "))
+                };
+                let header = egui::CollapsingHeader::new(title)
                     .default_open(*open_resources.get(&resource_name).unwrap_or(&false))
                     .show(ui, |ui| {
                         ui.push_id(format!("content-{:?}-{}", &type_id, &resource_name), |ui| {
@@ -286,7 +286,12 @@ fn clear_and_load_on_start(
     match save_confg.path.as_ref().unwrap() {
         space_shared::EditorPrefabPath::File(path) => {
             info!("Loading prefab from file {}", path);
-            load_server.scene = Some(assets.load(format!("{}.scn.ron", path)));
+            //Path without extension is a RON scene
+            if space_prefab::scene_format::is_scene_file(path) {
+                load_server.scene = Some(assets.load(path.clone()));
+            } else {
+                load_server.scene = Some(assets.load(format!("{}.scn.ron", path)));
+            }
         }
         space_shared::EditorPrefabPath::MemoryCache => {
             info!("Loading prefab from cache");
@@ -424,6 +429,7 @@ pub mod colors {
     pub const HYPERLINK_COLOR: Color32 = Color32::from_rgb(99, 235, 231);
     pub const WARM_COLOR: Color32 = Color32::from_rgb(225, 206, 67);
     pub const SELECTED_ITEM_COLOR: Color32 = Color32::from_rgb(76, 93, 235);
+    pub const OVERRIDE_COLOR: Color32 = Color32::from_rgb(95, 175, 255);
     pub const TEXT_COLOR: Color32 = Color32::WHITE;
 }
 
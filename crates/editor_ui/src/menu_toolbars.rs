@@ -10,9 +10,15 @@ use space_editor_core::{
     prelude::*,
     toast::{ClearToastMessage, ToastStorage},
 };
-use space_prefab::{component::GltfPrefab, load::PrefabBundle, plugins::PrefabPlugin};
+use space_prefab::{
+    component::GltfPrefab,
+    load::PrefabBundle,
+    plugins::PrefabPlugin,
+    save::PrefabPivot,
+    scene_format::{is_scene_file, strip_scene_extension},
+};
 use space_shared::{ext::egui_file, *};
-use space_undo::{AddedEntity, NewChange, RemovedEntity};
+use space_undo::{AddedEntity, NewChange, UndoDespawnExt};
 
 use crate::{
     colors::*,
@@ -143,7 +149,10 @@ pub struct MenuToolbarState {
     pub save_dialog: Option<egui_file::FileDialog>,
     pub load_dialog: Option<egui_file::FileDialog>,
     pub subscene_dialog: Option<egui_file::FileDialog>,
+    pub save_selection_dialog: Option<egui_file::FileDialog>,
     show_toasts: bool,
+    selection_pivot: PrefabPivot,
+    replace_selection: bool,
     pub path: String,
 }
 
@@ -175,12 +184,11 @@ pub fn bottom_menu(
                     .on_hover_text("Clear all entities")
                     .clicked()
                 {
-                    for (entity, _, _, _parent) in query.iter() {
-                        commands.entity(entity).despawn_recursive();
-
-                        changes.send(NewChange {
-                            change: Arc::new(RemovedEntity { entity }),
-                        });
+                    for (entity, _, _, parent) in query.iter() {
+                        //Children are despawned and restored together with their root
+                        if !parent.is_some_and(|parent| query.contains(parent.get())) {
+                            commands.entity(entity).despawn_recursive_with_undo();
+                        }
                     }
                 }
                 if ui
@@ -278,7 +286,9 @@ pub fn top_menu(
     mut events: EventReader<MenuLoadEvent>,
     mut menu_state: ResMut<MenuToolbarState>,
     mut editor_events: EventWriter<EditorEvent>,
+    mut save_selection_events: EventWriter<SaveSelectionAsPrefab>,
     mut clear_toast: EventWriter<ClearToastMessage>,
+    selected: Query<(), With<Selected>>,
     background_tasks: Res<BackgroundTaskStorage>,
     toasts: Res<ToastStorage>,
     sizing: Res<Sizing>,
@@ -297,10 +307,8 @@ pub fn top_menu(
                     .stroke(stroke_default_color());
                 if ui.add(open_button).clicked() {
                     let mut dialog = egui_file::FileDialog::open_file(Some("assets/".into()))
-                        .show_files_filter(Box::new(|path| {
-                            path.to_str().unwrap().ends_with(".scn.ron")
-                        }))
-                        .title("File Explorer (Scene/Bundle) (*.scn.ron)");
+                        .show_files_filter(Box::new(|path| is_scene_file(path.to_str().unwrap())))
+                        .title("File Explorer (Scene/Bundle) (*.scn.ron, *.scn.bin)");
                     dialog.open();
                     menu_state.file_dialog = Some(dialog);
                 }
@@ -312,12 +320,9 @@ pub fn top_menu(
                             //remove assets/ from path
                             if path.starts_with("assets/") {
                                 path = path.replace("assets/", "");
-                                //remove .scn.ron
-                                path = path.replace(".scn.ron", "");
-                                menu_state.path = path;
-                                editor_events.send(EditorEvent::Load(EditorPrefabPath::File(
-                                    format!("{}.scn.ron", menu_state.path.clone()),
-                                )));
+                                //remove scene extension
+                                menu_state.path = strip_scene_extension(&path).to_string();
+                                editor_events.send(EditorEvent::Load(EditorPrefabPath::File(path)));
                             }
                         }
                     } else {
@@ -357,12 +362,9 @@ pub fn top_menu(
                         if let Some(file) = save_dialog.path() {
                             let path = file.to_str().unwrap().to_string();
                             //remove assets/ from path
-                            if path.ends_with(".scn.ron") {
-                                let path = path.replace(".scn.ron", "");
+                            if is_scene_file(&path) {
                                 println!("{path}");
-                                editor_events.send(EditorEvent::Save(EditorPrefabPath::File(
-                                    format!("{}.scn.ron", path),
-                                )));
+                                editor_events.send(EditorEvent::Save(EditorPrefabPath::File(path)));
                             }
                         }
                     } else {
@@ -381,6 +383,73 @@ pub fn top_menu(
                 }
                 // End Save File
 
+                // Save selection as prefab
+                ui.add_enabled_ui(!selected.is_empty(), |ui| {
+                    ui.menu_button(to_richtext("🗐", &sizing.icon), |ui| {
+                        egui::ComboBox::from_label("Pivot")
+                            .selected_text(format!("{:?}", menu_state.selection_pivot))
+                            .show_ui(ui, |ui| {
+                                for pivot in [
+                                    PrefabPivot::Center,
+                                    PrefabPivot::BottomCenter,
+                                    PrefabPivot::WorldOrigin,
+                                ] {
+                                    ui.selectable_value(
+                                        &mut menu_state.selection_pivot,
+                                        pivot,
+                                        format!("{:?}", pivot),
+                                    );
+                                }
+                            });
+                        ui.checkbox(
+                            &mut menu_state.replace_selection,
+                            "Replace selection with prefab instance",
+                        );
+                        if ui.button("Save as prefab...").clicked() {
+                            let mut dialog =
+                                egui_file::FileDialog::save_file(Some("./assets/prefabs".into()))
+                                    .default_filename("Prefab0.scn.ron")
+                                    .title("Save Selection as Prefab");
+                            dialog.open();
+                            menu_state.save_selection_dialog = Some(dialog);
+                            ui.close_menu();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Save selection as prefab");
+                });
+
+                if let Some(dialog) = &mut menu_state.save_selection_dialog {
+                    if dialog.show(ctx).selected() {
+                        if let Some(file) = dialog.path() {
+                            let path = file.to_str().unwrap().replace('\\', "/");
+                            //Prefab path is relative to assets folder
+                            if let Some((_, path)) = path.split_once("assets/") {
+                                if is_scene_file(path) {
+                                    save_selection_events.send(SaveSelectionAsPrefab {
+                                        path: path.to_string(),
+                                        pivot: menu_state.selection_pivot,
+                                        replace_selection: menu_state.replace_selection,
+                                    });
+                                }
+                            }
+                        }
+                    } else {
+                        let mut need_move_to_default_dir = false;
+                        if let Some(path) = dialog.directory().to_str() {
+                            if !path.contains("assets") {
+                                need_move_to_default_dir = true;
+                            }
+                        } else {
+                            need_move_to_default_dir = true;
+                        }
+                        if need_move_to_default_dir {
+                            dialog.set_path("assets/");
+                        }
+                    }
+                }
+                // End Save selection as prefab
+
                 // Load Scene
                 let load_button = egui::Button::new(to_richtext("📤", &sizing.icon))
                     .stroke(stroke_default_color());
@@ -390,10 +459,8 @@ pub fn top_menu(
                     .clicked()
                 {
                     let mut dialog = egui_file::FileDialog::open_file(Some("assets/scenes".into()))
-                        .show_files_filter(Box::new(|path| {
-                            path.to_str().unwrap().ends_with(".scn.ron")
-                        }))
-                        .title("Load Scene (*.scn.ron)");
+                        .show_files_filter(Box::new(|path| is_scene_file(path.to_str().unwrap())))
+                        .title("Load Scene (*.scn.ron, *.scn.bin)");
                     dialog.open();
                     menu_state.load_dialog = Some(dialog);
                 }
@@ -405,12 +472,9 @@ pub fn top_menu(
                             //remove assets/ from path
                             if path.starts_with("assets/") {
                                 path = path.replace("assets/", "");
-                                //remove .scn.ron
-                                path = path.replace(".scn.ron", "");
-                                menu_state.path = path;
-                                editor_events.send(EditorEvent::Load(EditorPrefabPath::File(
-                                    format!("{}.scn.ron", menu_state.path.clone()),
-                                )));
+                                //remove scene extension
+                                menu_state.path = strip_scene_extension(&path).to_string();
+                                editor_events.send(EditorEvent::Load(EditorPrefabPath::File(path)));
                             }
                         }
                     } else {
@@ -484,11 +548,11 @@ pub fn top_menu(
                 {
                     let mut filedialog = egui_file::FileDialog::open_file(Some("assets".into()))
                         .show_files_filter(Box::new(|path| {
-                            path.to_str().unwrap().ends_with(".scn.ron")
+                            is_scene_file(path.to_str().unwrap())
                                 || path.to_str().unwrap().ends_with(".gltf")
                                 || path.to_str().unwrap().ends_with(".glb")
                         }))
-                        .title("Open Subscene (.scn.ron, .gltf, .glb)");
+                        .title("Open Subscene (.scn.ron, .scn.bin, .gltf, .glb)");
                     filedialog.open();
 
                     menu_state.subscene_dialog = Some(filedialog);
@@ -504,7 +568,7 @@ pub fn top_menu(
                                 path = path.trim_start_matches('\\').to_string();
                                 path = path.trim_start_matches('/').to_string();
 
-                                if path.ends_with(".scn.ron") {
+                                if is_scene_file(&path) {
                                     commands.spawn((PrefabBundle::new(&path), PrefabMarker));
                                 } else if path.ends_with(".gltf") || path.ends_with(".glb") {
                                     commands.spawn((
@@ -610,6 +674,9 @@ pub fn top_menu(
                             BackgroundTask::AssetLoading(path, _) => {
                                 ui.label(format!("Loading {}", path));
                             }
+                            BackgroundTask::SceneSaving(path) => {
+                                ui.label(format!("Saving {}", path));
+                            }
                             BackgroundTask::None => {}
                         }
                     }
@@ -618,11 +685,14 @@ pub fn top_menu(
         });
 
     for event in events.read() {
-        menu_state.path = event.path.clone();
-        editor_events.send(EditorEvent::Load(EditorPrefabPath::File(format!(
-            "{}.scn.ron",
-            menu_state.path.clone()
-        ))));
+        menu_state.path = strip_scene_extension(&event.path).to_string();
+        //Path without extension is a RON scene
+        let path = if is_scene_file(&event.path) {
+            event.path.clone()
+        } else {
+            format!("{}.scn.ron", event.path)
+        };
+        editor_events.send(EditorEvent::Load(EditorPrefabPath::File(path)));
     }
     events.clear();
 }
@@ -4,8 +4,9 @@ use bevy::{
 };
 use bevy_egui::*;
 use space_editor_core::hotkeys::AllHotkeys;
+use space_prefab::save::SceneSerializationSettings;
 use space_shared::ext::bevy_inspector_egui::bevy_inspector;
-use space_undo::ChangeChainSettings;
+use space_undo::{AppAutoUndo, ChangeChain, ChangeChainSettings};
 
 #[cfg(feature = "persistence_editor")]
 use space_persistence::*;
@@ -25,6 +26,8 @@ const TAB_MODES: [NewTabBehaviour; 3] = [
 
 const GAME_MODES: [GameMode; 2] = [GameMode::Game2D, GameMode::Game3D];
 
+const MEBIBYTE: f64 = 1024. * 1024.;
+
 pub struct SettingsWindowPlugin;
 
 impl Plugin for SettingsWindowPlugin {
@@ -36,13 +39,15 @@ impl Plugin for SettingsWindowPlugin {
             .register_type::<Sizing>()
             .register_type::<IconSize>()
             .register_type::<NewTabBehaviour>()
-            .init_resource::<NewWindowSettings>();
+            .init_resource::<NewWindowSettings>()
+            .auto_undo_resource::<GameModeSettings>();
         #[cfg(feature = "persistence_editor")]
         {
-            app.persistence_resource::<NewWindowSettings>()
-                .persistence_resource::<Sizing>()
-                .persistence_resource::<ChangeChainSettings>()
-                .persistence_resource::<GameModeSettings>();
+            app.persistence_resource_in_layer::<NewWindowSettings>(USER_LAYER)
+                .persistence_resource_in_layer::<Sizing>(USER_LAYER)
+                .persistence_resource_in_layer::<ChangeChainSettings>(USER_LAYER)
+                .persistence_resource_in_layer::<GameModeSettings>(PROJECT_LAYER)
+                .persistence_resource_in_layer::<SceneSerializationSettings>(PROJECT_LAYER);
         }
     }
 }
@@ -196,13 +201,45 @@ impl EditorTab for SettingsWindow {
         }
 
         ui.heading("Undo");
-        world.resource_scope::<ChangeChainSettings, _>(|_world, mut settings| {
+        world.resource_scope::<ChangeChainSettings, _>(|world, mut settings| {
             ui.add(
                 egui::DragValue::new(&mut settings.max_change_chain_size)
                     .prefix("Max change chain size: "),
             );
+            let mut memory_budget = settings.max_memory_usage as f64 / MEBIBYTE;
+            if ui
+                .add(
+                    egui::DragValue::new(&mut memory_budget)
+                        .clamp_range(1.0..=f64::MAX)
+                        .prefix("Memory budget: ")
+                        .suffix(" MiB"),
+                )
+                .changed()
+            {
+                settings.max_memory_usage = (memory_budget * MEBIBYTE) as usize;
+            }
+            let memory_usage = world.resource::<ChangeChain>().memory_usage();
+            ui.label(format!(
+                "Memory usage: {:.2} MiB ({:.0}%)",
+                memory_usage as f64 / MEBIBYTE,
+                memory_usage as f64 / settings.max_memory_usage as f64 * 100.
+            ));
+            ui.checkbox(
+                &mut settings.persistent_history,
+                "Save undo history with scene",
+            )
+            .on_hover_text("Undo history is restored when the same scene is opened again");
         });
 
+        ui.add_space(12.);
+        ui.heading("Scene Files");
+        let mut serialization = world.resource_mut::<SceneSerializationSettings>();
+        ui.checkbox(
+            &mut serialization.deterministic,
+            "Deterministic scene serialization",
+        )
+        .on_hover_text("Entities and components are saved in a stable order, which keeps diffs of scene files small");
+
         ui.add_space(12.);
         ui.heading("New Tab Behaviour");
         let new_window_settings = &mut world.resource_mut::<NewWindowSettings>();
@@ -0,0 +1,92 @@
+// Storages for persistence layers
+use std::path::PathBuf;
+
+use bevy::utils::HashMap;
+
+use crate::file::{read_persistence_file, write_persistence_file, PersistenceFile};
+use crate::{PersistenceDataSource, PERSISTENCE_FORMAT_VERSION};
+
+/// Number of backups kept by [`FilePersistenceBackend`]
+pub const DEFAULT_PERSISTENCE_BACKUPS: usize = 3;
+
+/// Everything stored in one persistence layer
+#[derive(Default, Clone, Debug)]
+pub struct PersistenceData {
+    /// Serialized resources by type path
+    pub data: HashMap<String, String>,
+    /// Versions of serialized resources. Missing entries have version 0
+    pub versions: HashMap<String, u32>,
+}
+
+/// Storage of one [`crate::PersistenceLayer`]. Layer is always read and written as a whole
+pub trait PersistenceBackend: Send + Sync + 'static {
+    /// Read all stored data. None if nothing was stored yet
+    fn read_all(&mut self) -> Result<Option<PersistenceData>, String>;
+
+    /// Replace all stored data
+    fn write_all(&mut self, data: &PersistenceData) -> Result<(), String>;
+}
+
+/// Ron file with rotating backups
+pub struct FilePersistenceBackend {
+    pub path: PathBuf,
+    /// Number of kept backups, 0 disables them
+    pub backups: usize,
+}
+
+impl FilePersistenceBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            backups: DEFAULT_PERSISTENCE_BACKUPS,
+        }
+    }
+}
+
+impl PersistenceBackend for FilePersistenceBackend {
+    fn read_all(&mut self) -> Result<Option<PersistenceData>, String> {
+        Ok(
+            read_persistence_file(&self.path, self.backups)?.map(|file| PersistenceData {
+                data: file.data,
+                versions: file.resource_versions,
+            }),
+        )
+    }
+
+    fn write_all(&mut self, data: &PersistenceData) -> Result<(), String> {
+        let file = PersistenceFile {
+            version: PERSISTENCE_FORMAT_VERSION,
+            resource_versions: data.versions.clone(),
+            data: data.data.clone(),
+        };
+        write_persistence_file(&self.path, &file, self.backups)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+}
+
+/// Keeps data only while the app is running.
+/// Useful for tests and for passing editor state to the game mode
+#[derive(Default)]
+pub struct MemoryPersistenceBackend {
+    pub stored: PersistenceData,
+}
+
+impl PersistenceBackend for MemoryPersistenceBackend {
+    fn read_all(&mut self) -> Result<Option<PersistenceData>, String> {
+        Ok(Some(self.stored.clone()))
+    }
+
+    fn write_all(&mut self, data: &PersistenceData) -> Result<(), String> {
+        self.stored = data.clone();
+        Ok(())
+    }
+}
+
+impl From<PersistenceDataSource> for Box<dyn PersistenceBackend> {
+    fn from(source: PersistenceDataSource) -> Self {
+        match source {
+            PersistenceDataSource::File(path) => Box::new(FilePersistenceBackend::new(path)),
+            PersistenceDataSource::Memory => Box::<MemoryPersistenceBackend>::default(),
+        }
+    }
+}
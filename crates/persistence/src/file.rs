@@ -0,0 +1,144 @@
+// Crash-safe reading and writing of persistence files
+use std::path::{Path, PathBuf};
+
+use bevy::{prelude::*, utils::HashMap};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+/// Version of the persistence file layout.
+/// Files without version are plain maps of serialized resources
+pub const PERSISTENCE_FORMAT_VERSION: u32 = 1;
+
+/// Content of one persistence file
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct PersistenceFile {
+    pub version: u32,
+    /// Version of every stored resource, see [`crate::AppPersistenceExt::persistence_migration`]
+    #[serde(default)]
+    pub resource_versions: HashMap<String, u32>,
+    pub data: HashMap<String, String>,
+}
+
+impl PersistenceFile {
+    fn parse(text: &str) -> Result<Self, String> {
+        match ron::from_str::<Self>(text) {
+            Ok(file) => Ok(file),
+            Err(err) => {
+                //Files of older editor versions
+                let data =
+                    ron::from_str::<HashMap<String, String>>(text).map_err(|_| err.to_string())?;
+                Ok(Self {
+                    version: 0,
+                    resource_versions: HashMap::default(),
+                    data,
+                })
+            }
+        }
+    }
+}
+
+/// Path of the backup with given number, 1 is the newest one
+pub fn backup_path(path: &Path, idx: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".bak{idx}"));
+    PathBuf::from(name)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Read persistence file. If it is damaged, backups are tried from the newest one.
+/// Returns None if neither the file nor its backups exist
+pub fn read_persistence_file(
+    path: &Path,
+    backups: usize,
+) -> Result<Option<PersistenceFile>, String> {
+    let candidates: Vec<PathBuf> = std::iter::once(path.to_path_buf())
+        .chain((1..=backups).map(|idx| backup_path(path, idx)))
+        .filter(|candidate| candidate.exists())
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    for candidate in candidates.iter() {
+        let res = std::fs::read_to_string(candidate)
+            .map_err(|err| err.to_string())
+            .and_then(|text| PersistenceFile::parse(&text));
+        match res {
+            Ok(file) => {
+                if candidate != path {
+                    warn!(
+                        "Persistence file {} is damaged, loaded backup {}",
+                        path.display(),
+                        candidate.display()
+                    );
+                }
+                if file.version > PERSISTENCE_FORMAT_VERSION {
+                    warn!(
+                        "Persistence file {} was written by newer editor version",
+                        candidate.display()
+                    );
+                }
+                return Ok(Some(file));
+            }
+            Err(err) => {
+                error!(
+                    "Failed to read persistence file {}: {}",
+                    candidate.display(),
+                    err
+                );
+            }
+        }
+    }
+    Err(format!(
+        "Persistence file {} and its backups are damaged",
+        path.display()
+    ))
+}
+
+/// Write persistence file without the risk of leaving it half written:
+/// data goes to a temporary file which replaces the old one after the previous
+/// version was moved to backups
+pub fn write_persistence_file(
+    path: &Path,
+    file: &PersistenceFile,
+    backups: usize,
+) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+    }
+    let text =
+        ron::ser::to_string_pretty(file, PrettyConfig::default()).map_err(|err| err.to_string())?;
+
+    let temp = temp_path(path);
+    {
+        use std::io::Write;
+        let mut temp_file = std::fs::File::create(&temp).map_err(|err| err.to_string())?;
+        temp_file
+            .write_all(text.as_bytes())
+            .and_then(|_| temp_file.sync_all())
+            .map_err(|err| err.to_string())?;
+    }
+
+    if backups > 0 && path.exists() {
+        rotate_backups(path, backups).map_err(|err| err.to_string())?;
+    }
+    std::fs::rename(&temp, path).map_err(|err| err.to_string())
+}
+
+fn rotate_backups(path: &Path, backups: usize) -> std::io::Result<()> {
+    for idx in (1..backups).rev() {
+        let from = backup_path(path, idx);
+        if from.exists() {
+            std::fs::rename(from, backup_path(path, idx + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
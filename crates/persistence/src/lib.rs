@@ -1,5 +1,7 @@
 #![allow(clippy::type_complexity)]
 
+mod backend;
+mod file;
 #[cfg(test)]
 mod tests;
 // This part of code is used for saving and loading settings and window state
@@ -7,13 +9,26 @@ use bevy::{
     prelude::*,
     reflect::{
         serde::{ReflectSerializer, UntypedReflectDeserializer},
-        GetTypeRegistration,
+        GetTypeRegistration, TypeRegistry,
     },
     utils::HashMap,
     window::WindowCloseRequested,
 };
 use ron::ser::PrettyConfig;
 use serde::de::DeserializeSeed;
+use std::{path::PathBuf, time::Duration};
+
+pub use backend::*;
+pub use file::PERSISTENCE_FORMAT_VERSION;
+
+/// Function which converts serialized resource of older version to the current one.
+/// Receives version of stored data and its ron text
+pub type PersistenceMigrationFn = Box<dyn Fn(u32, String) -> Result<String, String> + Send + Sync>;
+
+/// Layer for personal editor preferences, stored in the user config directory
+pub const USER_LAYER: &str = "user";
+/// Layer for settings shared by everyone working on the project, stored in the working directory
+pub const PROJECT_LAYER: &str = "project";
 
 /// Plugin that enables persistence for marked entities
 pub struct PersistencePlugin;
@@ -48,11 +63,13 @@ impl Plugin for PersistencePlugin {
 
         app.add_systems(
             Update,
-            persistence_start.in_set(PersistenceSet::EventReader),
+            (persistence_autosave, persistence_start)
+                .chain()
+                .in_set(PersistenceSet::EventReader),
         );
         app.add_systems(Update, persistence_end.in_set(PersistenceSet::Collect));
 
-        app.persistence_resource::<PersistenceSettings>();
+        app.persistence_resource_in_layer::<PersistenceSettings>(USER_LAYER);
     }
 }
 
@@ -66,6 +83,32 @@ fn persistence_save_on_close(
     }
 }
 
+/// Save persisted resources when they were not changed for [`PersistenceSettings::autosave_delay`]
+fn persistence_autosave(
+    settings: Res<PersistenceSettings>,
+    time: Res<Time<Real>>,
+    mut persistence: ResMut<PersistenceRegistry>,
+    mut events: EventWriter<PersistenceEvent>,
+) {
+    let now = time.elapsed();
+    if std::mem::take(&mut persistence.changed) {
+        persistence.last_change = Some(now);
+    }
+    if !settings.autosave {
+        persistence.last_change = None;
+        return;
+    }
+    let Some(last_change) = persistence.last_change else {
+        return;
+    };
+    if persistence.mode == PersistenceMode::None
+        && now.saturating_sub(last_change).as_secs_f32() >= settings.autosave_delay
+    {
+        persistence.last_change = None;
+        events.send(PersistenceEvent::Save);
+    }
+}
+
 fn persistence_startup_load(
     mut events: EventWriter<PersistenceEvent>,
     settings: Res<PersistenceSettings>,
@@ -83,23 +126,15 @@ fn persistence_start(
     for event in events.read() {
         match event {
             PersistenceEvent::Save => {
+                //Pending autosave is not needed anymore
+                persistence.last_change = None;
                 broadcast.send(PersistenceResourceBroadcastEvent::Pack);
                 persistence.mode = PersistenceMode::Saving;
                 persistence.save_counter = 0;
             }
             PersistenceEvent::Load => {
-                match &persistence.source {
-                    PersistenceDataSource::File(path) => {
-                        let Ok(file) = std::fs::File::open(path) else {
-                            warn!("Persistence file not found at path {}", path);
-                            continue;
-                        };
-                        let data: HashMap<String, String> = ron::de::from_reader(file).unwrap();
-                        persistence.data = data;
-                    }
-                    PersistenceDataSource::Memory => {
-                        //do nothing
-                    }
+                if !persistence.load_layers() {
+                    continue;
                 }
 
                 broadcast.send(PersistenceResourceBroadcastEvent::Unpack);
@@ -122,21 +157,7 @@ fn persistence_end(mut persistence: ResMut<PersistenceRegistry>) {
                 );
             }
 
-            match &persistence.source {
-                PersistenceDataSource::File(path) => {
-                    let mut file = std::fs::File::create(path).unwrap();
-                    ron::ser::to_writer_pretty(
-                        &mut file,
-                        &persistence.data,
-                        PrettyConfig::default(),
-                    )
-                    .unwrap();
-                }
-                PersistenceDataSource::Memory => {
-                    //do nothing
-                }
-            }
-            {}
+            persistence.save_layers();
         }
         PersistenceMode::Loading => {
             persistence.mode = PersistenceMode::None;
@@ -152,10 +173,14 @@ fn persistence_end(mut persistence: ResMut<PersistenceRegistry>) {
 }
 
 #[derive(Resource, Reflect)]
-#[reflect(Resource)]
+#[reflect(Resource, Default)]
 pub struct PersistenceSettings {
     pub load_on_startup: bool,
     pub save_on_close: bool,
+    /// Save when persisted resources were changed, so changes survive editor crash
+    pub autosave: bool,
+    /// Seconds without changes before autosave, so continuous edits are written once
+    pub autosave_delay: f32,
 }
 
 impl Default for PersistenceSettings {
@@ -163,6 +188,8 @@ impl Default for PersistenceSettings {
         Self {
             load_on_startup: true,
             save_on_close: true,
+            autosave: true,
+            autosave_delay: 2.0,
         }
     }
 }
@@ -180,17 +207,211 @@ enum PersistenceMode {
 /// all necessary data is saved to a file/memory, and then restored when the editor mode is opened.
 /// When the restored resource is loaded, the ['PersistenceLoaded<T>'] event is generated
 ///
+/// Data is split into [`PersistenceLayer`]s. By default these are [`USER_LAYER`] in the user
+/// config directory and [`PROJECT_LAYER`] in the working directory
+///
 /// ['PersistenceLoaded<T>']: crate::editor::core::persistence::PersistenceLoaded
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct PersistenceRegistry {
-    source: PersistenceDataSource,
+    /// Ordered by precedence, the last layer has the highest one
+    layers: Vec<PersistenceLayer>,
+    /// Layer of every registered resource by its type path
+    resource_layers: HashMap<String, String>,
+    /// Merged data of all layers
     data: HashMap<String, String>,
+    /// Versions of merged data entries. Missing entries have version 0
+    versions: HashMap<String, u32>,
+    /// Some persisted resource was changed since the last check of autosave
+    changed: bool,
+    /// Time of the last change which was not saved yet
+    last_change: Option<Duration>,
     load_counter: usize,
     save_counter: usize,
     target_count: usize,
     mode: PersistenceMode,
 }
 
+impl Default for PersistenceRegistry {
+    fn default() -> Self {
+        let mut layers = vec![];
+        if let Some(dir) = user_config_dir() {
+            let path = dir.join("space_editor").join("editor.ron");
+            layers.push(PersistenceLayer::new(
+                USER_LAYER,
+                Box::new(FilePersistenceBackend::new(path)),
+            ));
+        }
+        layers.push(PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::default().into(),
+        ));
+
+        Self {
+            layers,
+            resource_layers: HashMap::default(),
+            data: HashMap::default(),
+            versions: HashMap::default(),
+            changed: false,
+            last_change: None,
+            load_counter: 0,
+            save_counter: 0,
+            target_count: 0,
+            mode: PersistenceMode::default(),
+        }
+    }
+}
+
+impl PersistenceRegistry {
+    pub fn layers(&self) -> &[PersistenceLayer] {
+        &self.layers
+    }
+
+    /// Change storage of the layer with given name.
+    /// New layer is added with the highest precedence
+    pub fn set_layer(&mut self, name: &str, backend: Box<dyn PersistenceBackend>) {
+        if let Some(layer) = self.layers.iter_mut().find(|layer| layer.name == name) {
+            layer.backend = backend;
+        } else {
+            self.layers.push(PersistenceLayer::new(name, backend));
+        }
+    }
+
+    /// Index of the layer where the entry must be saved.
+    /// Entries of unknown layers go to the layer with the highest precedence
+    fn layer_index(&self, key: &str) -> Option<usize> {
+        self.resource_layers
+            .get(key)
+            .and_then(|name| self.layers.iter().position(|layer| &layer.name == name))
+            .or_else(|| self.layers.len().checked_sub(1))
+    }
+
+    /// Read all layers and merge their data. Entry from a layer with higher precedence
+    /// overrides the same entry from a lower one, but registered resource
+    /// always prefers its own layer. Returns false if no layer could be read
+    fn load_layers(&mut self) -> bool {
+        let mut loaded = false;
+        for layer in self.layers.iter_mut() {
+            match layer.backend.read_all() {
+                Ok(Some(stored)) => {
+                    layer.data = stored.data;
+                    layer.versions = stored.versions;
+                }
+                Ok(None) => {
+                    warn!("Persistence layer {} has no saved data", layer.name);
+                    continue;
+                }
+                Err(err) => {
+                    error!("Failed to read persistence layer {}: {}", layer.name, err);
+                    continue;
+                }
+            }
+            loaded = true;
+        }
+        if !loaded {
+            return false;
+        }
+
+        self.data.clear();
+        self.versions.clear();
+        for layer in self.layers.iter() {
+            for key in layer.data.keys() {
+                layer.copy_entry(key, &mut self.data, &mut self.versions);
+            }
+        }
+        for (key, name) in self.resource_layers.iter() {
+            if let Some(layer) = self
+                .layers
+                .iter()
+                .find(|layer| &layer.name == name && layer.data.contains_key(key))
+            {
+                layer.copy_entry(key, &mut self.data, &mut self.versions);
+            }
+        }
+        true
+    }
+
+    /// Split merged data back to layers and write them.
+    /// Registered resources are removed from all layers except their own one,
+    /// unknown entries stay in the layers they were loaded from
+    fn save_layers(&mut self) {
+        for (key, value) in self.data.iter() {
+            let registered = self.resource_layers.contains_key(key);
+            if !registered && self.layers.iter().any(|layer| layer.data.contains_key(key)) {
+                continue;
+            }
+            let Some(target) = self.layer_index(key) else {
+                continue;
+            };
+            let version = self.versions.get(key).copied();
+            for (idx, layer) in self.layers.iter_mut().enumerate() {
+                if idx == target {
+                    layer.data.insert(key.clone(), value.clone());
+                    match version {
+                        Some(version) => layer.versions.insert(key.clone(), version),
+                        None => layer.versions.remove(key),
+                    };
+                } else {
+                    layer.data.remove(key);
+                    layer.versions.remove(key);
+                }
+            }
+        }
+
+        for layer in self.layers.iter_mut() {
+            let stored = PersistenceData {
+                data: layer.data.clone(),
+                versions: layer.versions.clone(),
+            };
+            if let Err(err) = layer.backend.write_all(&stored) {
+                error!("Failed to write persistence layer {}: {}", layer.name, err);
+            }
+        }
+    }
+}
+
+/// Named part of persisted data with its own storage
+pub struct PersistenceLayer {
+    pub name: String,
+    pub backend: Box<dyn PersistenceBackend>,
+    data: HashMap<String, String>,
+    versions: HashMap<String, u32>,
+}
+
+impl PersistenceLayer {
+    pub fn new(name: impl Into<String>, backend: Box<dyn PersistenceBackend>) -> Self {
+        Self {
+            name: name.into(),
+            backend,
+            data: HashMap::default(),
+            versions: HashMap::default(),
+        }
+    }
+
+    fn copy_entry(
+        &self,
+        key: &str,
+        data: &mut HashMap<String, String>,
+        versions: &mut HashMap<String, u32>,
+    ) {
+        data.insert(key.to_string(), self.data[key].clone());
+        match self.versions.get(key) {
+            Some(version) => versions.insert(key.to_string(), *version),
+            None => versions.remove(key),
+        };
+    }
+}
+
+/// Platform directory for user config files
+fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
 #[derive(Event, Default)]
 pub struct PersistenceLoaded<T> {
     _phantom: std::marker::PhantomData<T>,
@@ -208,6 +429,7 @@ enum PersistenceResourceBroadcastEvent {
     Pack,
 }
 
+/// Built-in storages of persistence layers, see [`PersistenceBackend`] for custom ones
 #[derive(Reflect, Clone)]
 #[reflect(Default)]
 pub enum PersistenceDataSource {
@@ -224,6 +446,9 @@ impl Default for PersistenceDataSource {
 #[derive(Resource)]
 struct PersistenceLoadPipeline<T> {
     pub load_fn: Box<dyn Fn(&mut T, T) + Send + Sync>,
+    /// Current version of the resource data
+    pub version: u32,
+    pub migration_fn: Option<PersistenceMigrationFn>,
 }
 
 impl<T> Default for PersistenceLoadPipeline<T> {
@@ -232,42 +457,69 @@ impl<T> Default for PersistenceLoadPipeline<T> {
             load_fn: Box::new(|dst, src| {
                 *dst = src;
             }),
+            version: 0,
+            migration_fn: None,
         }
     }
 }
 
+/// Resources registered without layer are stored in [`PROJECT_LAYER`]
 pub trait AppPersistenceExt {
     fn persistence_resource<T: Default + Reflect + FromReflect + Resource + GetTypeRegistration>(
         &mut self,
     ) -> &mut Self;
 
+    fn persistence_resource_in_layer<
+        T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
+    >(
+        &mut self,
+        layer: &str,
+    ) -> &mut Self;
+
     fn persistence_resource_with_fn<
         T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
     >(
         &mut self,
         load_function: Box<dyn Fn(&mut T, T) + Send + Sync>,
     ) -> &mut Self;
+
+    fn persistence_resource_with_fn_in_layer<
+        T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
+    >(
+        &mut self,
+        layer: &str,
+        load_function: Box<dyn Fn(&mut T, T) + Send + Sync>,
+    ) -> &mut Self;
+
+    /// Set storage of the persistence layer. New layer gets the highest precedence
+    fn persistence_layer(&mut self, name: &str, backend: impl PersistenceBackend) -> &mut Self;
+
+    /// Set current version of the resource data and the function which converts older data.
+    /// Stored data without version has version 0
+    fn persistence_migration<T: Default + Reflect + FromReflect + Resource + GetTypeRegistration>(
+        &mut self,
+        version: u32,
+        migration: PersistenceMigrationFn,
+    ) -> &mut Self;
 }
 
 impl AppPersistenceExt for App {
     fn persistence_resource<T: Default + Reflect + FromReflect + Resource + GetTypeRegistration>(
         &mut self,
     ) -> &mut Self {
-        self.world
-            .resource_mut::<PersistenceRegistry>()
-            .target_count += 1;
-
-        self.register_type::<T>();
-        self.add_event::<PersistenceLoaded<T>>();
-
-        self.init_resource::<PersistenceLoadPipeline<T>>();
-
-        self.add_systems(
-            Update,
-            persistence_resource_system::<T>.in_set(PersistenceSet::ResourceProcess),
-        );
+        self.persistence_resource_in_layer::<T>(PROJECT_LAYER)
+    }
 
-        self
+    fn persistence_resource_in_layer<
+        T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
+    >(
+        &mut self,
+        layer: &str,
+    ) -> &mut Self {
+        self.persistence_resource_with_fn_in_layer::<T>(
+            layer,
+            PersistenceLoadPipeline::<T>::default().load_fn,
+        )
     }
 
     fn persistence_resource_with_fn<
@@ -276,16 +528,33 @@ impl AppPersistenceExt for App {
         &mut self,
         load_function: Box<dyn Fn(&mut T, T) + Send + Sync>,
     ) -> &mut Self {
-        self.world
-            .resource_mut::<PersistenceRegistry>()
-            .target_count += 1;
+        self.persistence_resource_with_fn_in_layer::<T>(PROJECT_LAYER, load_function)
+    }
+
+    fn persistence_resource_with_fn_in_layer<
+        T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
+    >(
+        &mut self,
+        layer: &str,
+        load_function: Box<dyn Fn(&mut T, T) + Send + Sync>,
+    ) -> &mut Self {
+        let mut registry = self.world.resource_mut::<PersistenceRegistry>();
+        registry.target_count += 1;
+        registry.resource_layers.insert(
+            T::get_type_registration()
+                .type_info()
+                .type_path()
+                .to_string(),
+            layer.to_string(),
+        );
 
         self.register_type::<T>();
         self.add_event::<PersistenceLoaded<T>>();
 
-        self.insert_resource(PersistenceLoadPipeline {
-            load_fn: load_function,
-        });
+        //Migration can be set before registration
+        self.world
+            .get_resource_or_insert_with(PersistenceLoadPipeline::<T>::default)
+            .load_fn = load_function;
 
         self.add_systems(
             Update,
@@ -294,6 +563,28 @@ impl AppPersistenceExt for App {
 
         self
     }
+
+    fn persistence_layer(&mut self, name: &str, backend: impl PersistenceBackend) -> &mut Self {
+        self.world
+            .resource_mut::<PersistenceRegistry>()
+            .set_layer(name, Box::new(backend));
+        self
+    }
+
+    fn persistence_migration<
+        T: Default + Reflect + FromReflect + Resource + GetTypeRegistration,
+    >(
+        &mut self,
+        version: u32,
+        migration: PersistenceMigrationFn,
+    ) -> &mut Self {
+        let mut pipeline = self
+            .world
+            .get_resource_or_insert_with(PersistenceLoadPipeline::<T>::default);
+        pipeline.version = version;
+        pipeline.migration_fn = Some(migration);
+        self
+    }
 }
 
 fn persistence_resource_system<
@@ -305,38 +596,60 @@ fn persistence_resource_system<
     registry: Res<AppTypeRegistry>,
     mut persistence_loaded: EventWriter<PersistenceLoaded<T>>,
     pipeline: ResMut<PersistenceLoadPipeline<T>>,
+    mut loaded: Local<bool>,
 ) {
+    //Changes made by loading are already saved
+    if resource.is_changed() && !resource.is_added() && !std::mem::take(&mut *loaded) {
+        persistence.changed = true;
+    }
+
     for event in events.read() {
         match event {
             PersistenceResourceBroadcastEvent::Pack => {
                 let type_registry = registry.read();
-                let serializer = ReflectSerializer::new(resource.as_ref(), &type_registry);
-                let data = ron::to_string(&serializer).unwrap();
-                persistence.data.insert(
-                    T::get_type_registration()
-                        .type_info()
-                        .type_path()
-                        .to_string(),
-                    data,
-                );
+                let data = match serialize_reflect(resource.as_ref(), &type_registry) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        error!(
+                            "Persistence resource {} could not be serialized: {}",
+                            T::get_type_registration().type_info().type_path(),
+                            err
+                        );
+                        continue;
+                    }
+                };
+                let key = T::get_type_registration()
+                    .type_info()
+                    .type_path()
+                    .to_string();
+                persistence.versions.insert(key.clone(), pipeline.version);
+                persistence.data.insert(key, data);
                 persistence.save_counter += 1;
             }
             PersistenceResourceBroadcastEvent::Unpack => {
-                let Some(data) = persistence
-                    .data
-                    .get(T::get_type_registration().type_info().type_path())
-                else {
-                    warn!(
-                        "Persistence resource {} not found",
-                        T::get_type_registration().type_info().type_path()
-                    );
+                let key = T::get_type_registration().type_info().type_path();
+                let Some(data) = persistence.data.get(key).cloned() else {
+                    warn!("Persistence resource {} not found", key);
                     continue;
                 };
+                let stored_version = persistence.versions.get(key).copied().unwrap_or(0);
+                let data = match &pipeline.migration_fn {
+                    Some(migration) if stored_version < pipeline.version => {
+                        match migration(stored_version, data) {
+                            Ok(data) => data,
+                            Err(err) => {
+                                warn!(
+                                    "Persistence resource {} could not be migrated from version {}: {}",
+                                    key, stored_version, err
+                                );
+                                continue;
+                            }
+                        }
+                    }
+                    _ => data,
+                };
                 let type_registry = registry.read();
-                let deserializer = UntypedReflectDeserializer::new(&type_registry);
-                let Ok(reflected_value) =
-                    deserializer.deserialize(&mut ron::Deserializer::from_str(data).unwrap())
-                else {
+                let Ok(reflected_value) = deserialize_reflect(&data, &type_registry) else {
                     warn!(
                         "Persistence resource {} could not be deserialized",
                         T::get_type_registration().type_info().type_path()
@@ -353,6 +666,7 @@ fn persistence_resource_system<
                 };
                 (pipeline.load_fn)(resource.as_mut(), converted);
                 resource.set_changed();
+                *loaded = true;
 
                 persistence_loaded.send(PersistenceLoaded::<T>::default());
                 persistence.load_counter += 1;
@@ -360,3 +674,43 @@ fn persistence_resource_system<
         }
     }
 }
+
+/// Serialize reflected value to ron string in the same format as persistence file entries
+pub fn serialize_reflect(value: &dyn Reflect, registry: &TypeRegistry) -> Result<String, String> {
+    let serializer = ReflectSerializer::new(value, registry);
+    ron::to_string(&serializer).map_err(|err| err.to_string())
+}
+
+/// Deserialize value which was serialized by [`serialize_reflect`].
+/// Result is a dynamic value, use [`FromReflect`] to get concrete type
+pub fn deserialize_reflect(
+    data: &str,
+    registry: &TypeRegistry,
+) -> Result<Box<dyn Reflect>, String> {
+    let mut ron_deserializer = ron::Deserializer::from_str(data).map_err(|err| err.to_string())?;
+    UntypedReflectDeserializer::new(registry)
+        .deserialize(&mut ron_deserializer)
+        .map_err(|err| err.to_string())
+}
+
+/// Write reflected value to standalone ron file.
+/// Can be used for editor data which must not be stored in the common persistence file
+pub fn save_reflect_file(
+    path: impl AsRef<std::path::Path>,
+    value: &dyn Reflect,
+    registry: &TypeRegistry,
+) -> Result<(), String> {
+    let serializer = ReflectSerializer::new(value, registry);
+    let data = ron::ser::to_string_pretty(&serializer, PrettyConfig::default())
+        .map_err(|err| err.to_string())?;
+    std::fs::write(path, data).map_err(|err| err.to_string())
+}
+
+/// Read value written by [`save_reflect_file`]
+pub fn load_reflect_file(
+    path: impl AsRef<std::path::Path>,
+    registry: &TypeRegistry,
+) -> Result<Box<dyn Reflect>, String> {
+    let data = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    deserialize_reflect(&data, registry)
+}
@@ -24,6 +24,7 @@ fn save_on_close_false_doesnt_triggers_event() {
     app.insert_resource(PersistenceSettings {
         load_on_startup: true,
         save_on_close: false,
+        ..Default::default()
     })
     .add_event::<PersistenceEvent>()
     .add_event::<WindowCloseRequested>()
@@ -98,7 +99,10 @@ fn persistence_starts_on_save() {
 fn persistence_starts_on_load_mem() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
-        source: PersistenceDataSource::Memory,
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::Memory.into(),
+        )],
         ..Default::default()
     })
     .add_event::<PersistenceEvent>()
@@ -127,7 +131,10 @@ fn persistence_starts_on_load_mem() {
 fn persistence_starts_on_load_file() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
-        source: PersistenceDataSource::File(String::from("../../test_data/test_editor.ron")),
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::File(String::from("../../test_data/test_editor.ron")).into(),
+        )],
         ..Default::default()
     })
     .add_event::<PersistenceEvent>()
@@ -159,7 +166,10 @@ fn persistence_starts_on_load_file() {
 fn persistence_starts_on_file_not_found() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
-        source: PersistenceDataSource::File(String::from("../../test_data/fake_editor.ron")),
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::File(String::from("../../test_data/fake_editor.ron")).into(),
+        )],
         ..Default::default()
     })
     .add_event::<PersistenceEvent>()
@@ -185,7 +195,10 @@ fn persistence_starts_on_file_not_found() {
 fn persistence_starts_on_load_from_memory() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
-        source: PersistenceDataSource::Memory,
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::Memory.into(),
+        )],
         ..Default::default()
     })
     .add_event::<PersistenceEvent>()
@@ -241,7 +254,10 @@ fn persistence_end_from_saving_mem() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
         mode: PersistenceMode::Saving,
-        source: PersistenceDataSource::Memory,
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::Memory.into(),
+        )],
         ..Default::default()
     })
     .add_systems(PreUpdate, persistence_end);
@@ -257,7 +273,10 @@ fn persistence_end_from_saving_file() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
         mode: PersistenceMode::Saving,
-        source: PersistenceDataSource::File("../../target/fake_editor.ron".to_string()),
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::File("../../target/fake_editor.ron".to_string()).into(),
+        )],
         data: HashMap::from([("test".to_string(), "hello world".to_string())]),
         ..Default::default()
     })
@@ -276,7 +295,7 @@ fn persistence_system_unpack() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
         mode: PersistenceMode::Loading,
-        source: PersistenceDataSource::Memory,
+        layers: vec![PersistenceLayer::new(PROJECT_LAYER, PersistenceDataSource::Memory.into())],
         data: HashMap::from([("space_persistence::PersistenceSettings".to_string(), "{\"space_persistence::PersistenceSettings\":(load_on_startup:true,save_on_close:false)}".to_string())]),
         ..Default::default()
     })
@@ -313,7 +332,7 @@ fn persistence_system_pack() {
     let mut app = App::new();
     app.insert_resource(PersistenceRegistry {
         mode: PersistenceMode::Saving,
-        source: PersistenceDataSource::File("../../target/persistence_test.ron".to_string()),
+        layers: vec![PersistenceLayer::new(PROJECT_LAYER, PersistenceDataSource::File("../../target/persistence_test.ron".to_string()).into())],
         data: HashMap::from([("space_persistence::PersistenceSettings".to_string(), "{\"space_persistence::PersistenceSettings\":(load_on_startup:true,save_on_close:false)}".to_string())]),
         ..Default::default()
     })
@@ -338,3 +357,286 @@ fn persistence_system_pack() {
     let reg = app.world.resource::<PersistenceRegistry>();
     assert_eq!(reg.save_counter, 1)
 }
+
+#[test]
+fn reflect_file_roundtrip() {
+    let mut registry = TypeRegistry::default();
+    registry.register::<PersistenceSettings>();
+    let path = "../../target/reflect_file_test.ron";
+
+    let settings = PersistenceSettings {
+        load_on_startup: false,
+        save_on_close: true,
+        ..Default::default()
+    };
+    save_reflect_file(path, &settings, &registry).unwrap();
+
+    let loaded = load_reflect_file(path, &registry).unwrap();
+    let loaded = PersistenceSettings::from_reflect(loaded.as_ref()).unwrap();
+    assert!(!loaded.load_on_startup);
+    assert!(loaded.save_on_close);
+}
+
+fn memory_layer(name: &str, data: &[(&str, &str)]) -> PersistenceLayer {
+    let backend = MemoryPersistenceBackend {
+        stored: PersistenceData {
+            data: data
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        },
+    };
+    PersistenceLayer::new(name, Box::new(backend))
+}
+
+#[test]
+fn persistence_layers_merge_on_load() {
+    let mut registry = PersistenceRegistry {
+        layers: vec![
+            memory_layer(USER_LAYER, &[("a", "user"), ("b", "user"), ("c", "user")]),
+            memory_layer(PROJECT_LAYER, &[("b", "project"), ("c", "project")]),
+        ],
+        resource_layers: HashMap::from([("c".to_string(), USER_LAYER.to_string())]),
+        ..Default::default()
+    };
+
+    assert!(registry.load_layers());
+
+    assert_eq!(registry.data["a"], "user");
+    assert_eq!(registry.data["b"], "project");
+    //Registered resource prefers its own layer
+    assert_eq!(registry.data["c"], "user");
+}
+
+#[test]
+fn persistence_layers_split_on_save() {
+    let mut registry = PersistenceRegistry {
+        layers: vec![
+            memory_layer(USER_LAYER, &[("unknown", "user")]),
+            memory_layer(PROJECT_LAYER, &[("hotkeys", "old")]),
+        ],
+        resource_layers: HashMap::from([
+            ("hotkeys".to_string(), USER_LAYER.to_string()),
+            ("game".to_string(), PROJECT_LAYER.to_string()),
+        ]),
+        ..Default::default()
+    };
+    registry.load_layers();
+    registry
+        .data
+        .insert("hotkeys".to_string(), "new".to_string());
+    registry.data.insert("game".to_string(), "3d".to_string());
+    registry
+        .data
+        .insert("other".to_string(), "value".to_string());
+
+    registry.save_layers();
+
+    let user = &registry.layers[0].data;
+    let project = &registry.layers[1].data;
+    assert_eq!(user["hotkeys"], "new");
+    assert_eq!(user["unknown"], "user");
+    assert!(!project.contains_key("hotkeys"));
+    assert_eq!(project["game"], "3d");
+    assert_eq!(project["other"], "value");
+}
+
+/// Backend which shares its storage with the test
+struct SharedBackend(std::sync::Arc<std::sync::Mutex<Option<PersistenceData>>>);
+
+impl PersistenceBackend for SharedBackend {
+    fn read_all(&mut self) -> Result<Option<PersistenceData>, String> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn write_all(&mut self, data: &PersistenceData) -> Result<(), String> {
+        *self.0.lock().unwrap() = Some(data.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn persistence_custom_backend() {
+    let storage = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let mut registry = PersistenceRegistry {
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::File("project.ron".to_string()).into(),
+        )],
+        resource_layers: HashMap::from([("hotkeys".to_string(), USER_LAYER.to_string())]),
+        ..Default::default()
+    };
+    registry.set_layer(USER_LAYER, Box::new(SharedBackend(storage.clone())));
+    registry.set_layer(PROJECT_LAYER, PersistenceDataSource::Memory.into());
+
+    let names: Vec<_> = registry.layers().iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, [PROJECT_LAYER, USER_LAYER]);
+
+    registry
+        .data
+        .insert("hotkeys".to_string(), "bindings".to_string());
+    registry.versions.insert("hotkeys".to_string(), 2);
+    registry.save_layers();
+
+    let stored = storage.lock().unwrap().clone().unwrap();
+    assert_eq!(stored.data["hotkeys"], "bindings");
+    assert_eq!(stored.versions["hotkeys"], 2);
+
+    registry.data.clear();
+    assert!(registry.load_layers());
+    assert_eq!(registry.data["hotkeys"], "bindings");
+}
+
+#[test]
+fn persistence_file_backups() {
+    let path = std::path::Path::new("../../target/persistence_backup_test.ron");
+    for idx in 0..3 {
+        let _ = std::fs::remove_file(file::backup_path(path, idx + 1));
+    }
+    let mut backend = FilePersistenceBackend::new(path);
+    backend.backups = 2;
+
+    for idx in 0..4 {
+        let data = PersistenceData {
+            data: HashMap::from([("idx".to_string(), idx.to_string())]),
+            ..Default::default()
+        };
+        backend.write_all(&data).unwrap();
+    }
+
+    let read = |path: &std::path::Path| {
+        file::read_persistence_file(path, 0).unwrap().unwrap().data["idx"].clone()
+    };
+    assert_eq!(read(path), "3");
+    assert_eq!(read(&file::backup_path(path, 1)), "2");
+    assert_eq!(read(&file::backup_path(path, 2)), "1");
+    assert!(!file::backup_path(path, 3).exists());
+
+    //Half written file falls back to the newest backup
+    std::fs::write(path, "(version: 1, data: {\"idx\": ").unwrap();
+    let restored = backend.read_all().unwrap().unwrap();
+    assert_eq!(restored.data["idx"], "2");
+    backend.backups = 0;
+    assert!(backend.read_all().is_err());
+}
+
+#[test]
+fn persistence_migration_renamed_field() {
+    let mut app = App::new();
+    app.insert_resource(PersistenceRegistry {
+        mode: PersistenceMode::Loading,
+        layers: vec![PersistenceLayer::new(PROJECT_LAYER, PersistenceDataSource::Memory.into())],
+        data: HashMap::from([("space_persistence::PersistenceSettings".to_string(), "{\"space_persistence::PersistenceSettings\":(load_on_startup:false,save_on_exit:false)}".to_string())]),
+        ..Default::default()
+    })
+    .init_resource::<PersistenceSettings>()
+    .add_event::<PersistenceEvent>()
+    .add_event::<PersistenceResourceBroadcastEvent>();
+    app.persistence_resource::<PersistenceSettings>()
+        .persistence_migration::<PersistenceSettings>(
+            1,
+            Box::new(|version, data| {
+                assert_eq!(version, 0);
+                Ok(data.replace("save_on_exit", "save_on_close"))
+            }),
+        );
+    app.update();
+    app.world
+        .send_event(PersistenceResourceBroadcastEvent::Unpack);
+    app.update();
+
+    let settings = app.world.resource::<PersistenceSettings>();
+    assert!(!settings.load_on_startup);
+    assert!(!settings.save_on_close);
+    assert_eq!(app.world.resource::<PersistenceRegistry>().load_counter, 1);
+
+    app.world
+        .send_event(PersistenceResourceBroadcastEvent::Pack);
+    app.update();
+    let registry = app.world.resource::<PersistenceRegistry>();
+    assert_eq!(
+        registry.versions["space_persistence::PersistenceSettings"],
+        1
+    );
+}
+
+fn configure_autosave_app(autosave_delay: f32) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    app.insert_resource(PersistenceRegistry {
+        layers: vec![PersistenceLayer::new(
+            PROJECT_LAYER,
+            PersistenceDataSource::Memory.into(),
+        )],
+        ..Default::default()
+    })
+    .insert_resource(PersistenceSettings {
+        autosave_delay,
+        ..Default::default()
+    })
+    .add_event::<PersistenceEvent>()
+    .add_event::<PersistenceResourceBroadcastEvent>()
+    .add_systems(Update, persistence_autosave);
+    app.persistence_resource::<PersistenceSettings>();
+    app.update();
+    app
+}
+
+fn save_events(app: &App) -> usize {
+    app.world.resource::<Events<PersistenceEvent>>().len()
+}
+
+#[test]
+fn autosave_after_change() {
+    let mut app = configure_autosave_app(0.0);
+    app.update();
+    assert_eq!(save_events(&app), 0);
+
+    app.world
+        .resource_mut::<PersistenceSettings>()
+        .save_on_close = false;
+    app.update();
+    app.update();
+    assert_eq!(save_events(&app), 1);
+    assert!(app
+        .world
+        .resource::<PersistenceRegistry>()
+        .last_change
+        .is_none());
+}
+
+#[test]
+fn autosave_waits_for_quiet_period() {
+    let mut app = configure_autosave_app(1000.0);
+    app.world
+        .resource_mut::<PersistenceSettings>()
+        .save_on_close = false;
+    app.update();
+    app.update();
+
+    assert_eq!(save_events(&app), 0);
+    assert!(app
+        .world
+        .resource::<PersistenceRegistry>()
+        .last_change
+        .is_some());
+}
+
+#[test]
+fn autosave_ignores_loaded_values() {
+    let mut app = configure_autosave_app(0.0);
+    app.world.resource_mut::<PersistenceRegistry>().data = HashMap::from([(
+        "space_persistence::PersistenceSettings".to_string(),
+        "{\"space_persistence::PersistenceSettings\":(load_on_startup:true,save_on_close:false)}"
+            .to_string(),
+    )]);
+    app.world
+        .send_event(PersistenceResourceBroadcastEvent::Unpack);
+    app.update();
+    app.update();
+    app.update();
+
+    assert!(!app.world.resource::<PersistenceSettings>().save_on_close);
+    assert_eq!(save_events(&app), 0);
+}
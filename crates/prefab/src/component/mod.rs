@@ -23,6 +23,8 @@ pub mod path;
 
 use bevy::{prelude::*, reflect::*, utils::HashMap};
 
+use crate::guid::PrefabGuid;
+
 /// External dependencies
 pub mod ext {
     pub use space_shared::ext::*;
@@ -114,16 +116,21 @@ impl<T: Reflect + FromReflect + Default + Clone> AutoStruct<T> {
 }
 
 /// This component used in prefab to determine links between entities. It is needed to create custom UI in `bevy_inspector_egui`. You must implement the [`MapEntities`](bevy::ecs::entity::MapEntities) trait for your component to make it work. See the `FollowCamera` struct from `examples/platformer.rs`.
+/// Link also stores [`PrefabGuid`] of the linked entity, so it is restored after load even if the entity is in another scene
 #[derive(Reflect, Clone)]
 #[reflect(Default)]
 pub struct EntityLink {
     pub entity: Entity,
+    /// Persistent id of the linked entity. It is stored on save and used to find the entity after load
+    #[reflect(default)]
+    pub guid: Option<PrefabGuid>,
 }
 
 impl Default for EntityLink {
     fn default() -> Self {
         Self {
             entity: Entity::PLACEHOLDER,
+            guid: None,
         }
     }
 }
@@ -152,6 +159,7 @@ mod tests {
     fn default_entity_link() {
         let entity_link = EntityLink::default();
         assert_eq!(entity_link.entity, Entity::PLACEHOLDER);
+        assert_eq!(entity_link.guid, None);
     }
 
     #[test]
@@ -0,0 +1,413 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    reflect::TypeRegistry,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    component::{AssetMesh, GltfPrefab, MaterialPrefab},
+    load::{is_prefab_variant, PrefabLoader, PrefabVariant},
+    scene_format::{is_scene_file, SceneFormat},
+};
+
+/// Keeps [`PrefabDependencyGraph`] up to date with loaded prefab files
+pub struct PrefabDependencyPlugin;
+
+impl Plugin for PrefabDependencyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrefabDependencyGraph>();
+        app.add_systems(Update, update_prefab_dependencies);
+    }
+}
+
+/// Graph of prefab files which are used by other prefab files.
+/// Files are added when they are loaded or after [`PrefabDependencyGraph::scan_folder`]
+#[derive(Resource, Default, Debug, Clone)]
+pub struct PrefabDependencyGraph {
+    dependencies: HashMap<String, HashSet<String>>,
+}
+
+impl PrefabDependencyGraph {
+    /// Replace prefab files used by the file
+    pub fn set_dependencies(&mut self, path: &str, dependencies: impl IntoIterator<Item = String>) {
+        self.dependencies.insert(
+            normalize_prefab_path(path),
+            dependencies
+                .into_iter()
+                .map(|dependency| normalize_prefab_path(&dependency))
+                .collect(),
+        );
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.dependencies.remove(&normalize_prefab_path(path));
+    }
+
+    /// All files known by the graph
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.dependencies.keys().map(|path| path.as_str())
+    }
+
+    /// Prefab files which are used directly by the file
+    pub fn dependencies(&self, path: &str) -> Vec<String> {
+        let mut dependencies: Vec<String> = self
+            .dependencies
+            .get(&normalize_prefab_path(path))
+            .map(|dependencies| dependencies.iter().cloned().collect())
+            .unwrap_or_default();
+        dependencies.sort();
+        dependencies
+    }
+
+    /// Files which contain the prefab directly or through other prefabs.
+    /// All of them are changed when the prefab is edited
+    pub fn users(&self, path: &str) -> Vec<String> {
+        let path = normalize_prefab_path(path);
+        let mut users = HashSet::new();
+        let mut stack = vec![path.clone()];
+        while let Some(current) = stack.pop() {
+            for (user, dependencies) in self.dependencies.iter() {
+                if dependencies.contains(&current) && users.insert(user.clone()) {
+                    stack.push(user.clone());
+                }
+            }
+        }
+        users.remove(&path);
+
+        let mut users: Vec<String> = users.into_iter().collect();
+        users.sort();
+        users
+    }
+
+    /// Chain of files which leads from the file back to itself, if there is one
+    pub fn find_cycle(&self, path: &str) -> Option<Vec<String>> {
+        let path = normalize_prefab_path(path);
+        let mut visited = HashSet::from([path.clone()]);
+        let mut chain = vec![path.clone()];
+        if self.find_path(&path, &path, &mut visited, &mut chain) {
+            Some(chain)
+        } else {
+            None
+        }
+    }
+
+    /// Chain of files which would become a cycle if the scene includes the prefab
+    pub fn find_cycle_with(&self, scene: &str, prefab: &str) -> Option<Vec<String>> {
+        let scene = normalize_prefab_path(scene);
+        let prefab = normalize_prefab_path(prefab);
+        let mut visited = HashSet::from([prefab.clone()]);
+        let mut chain = vec![scene.clone(), prefab.clone()];
+        if scene == prefab || self.find_path(&prefab, &scene, &mut visited, &mut chain) {
+            Some(chain)
+        } else {
+            None
+        }
+    }
+
+    fn find_path(
+        &self,
+        from: &str,
+        to: &str,
+        visited: &mut HashSet<String>,
+        chain: &mut Vec<String>,
+    ) -> bool {
+        let Some(dependencies) = self.dependencies.get(from) else {
+            return false;
+        };
+        let mut dependencies: Vec<&String> = dependencies.iter().collect();
+        dependencies.sort();
+        for dependency in dependencies {
+            if dependency == to {
+                chain.push(dependency.clone());
+                return true;
+            }
+            if visited.insert(dependency.clone()) {
+                chain.push(dependency.clone());
+                if self.find_path(dependency, to, visited, chain) {
+                    return true;
+                }
+                chain.pop();
+            }
+        }
+        false
+    }
+
+    /// Read all prefab files in the folder and its subfolders.
+    /// Paths are stored relative to the folder, like asset paths. Returns errors of files which can not be read
+    pub fn scan_folder(&mut self, folder: &Path, registry: &TypeRegistry) -> Vec<String> {
+        let mut errors = vec![];
+        let mut stack = vec![folder.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    errors.push(format!("{}: {}", dir.display(), err));
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                let file = entry.path();
+                if file.is_dir() {
+                    stack.push(file);
+                    continue;
+                }
+                let Ok(relative) = file.strip_prefix(folder) else {
+                    continue;
+                };
+                let path = relative.to_string_lossy().to_string();
+                if !is_prefab_file(&path) {
+                    continue;
+                }
+                let res = std::fs::read(&file)
+                    .map_err(|err| err.to_string())
+                    .and_then(|bytes| read_prefab_dependencies(&path, &bytes, registry));
+                match res {
+                    Ok(dependencies) => self.set_dependencies(&path, dependencies),
+                    Err(err) => errors.push(format!("{}: {}", file.display(), err)),
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// Asset paths of the same file can be written in different ways
+pub fn normalize_prefab_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_string()
+}
+
+/// Is file a scene or a prefab variant
+pub fn is_prefab_file(path: &str) -> bool {
+    is_scene_file(path) || is_prefab_variant(path)
+}
+
+/// Prefab files which are loaded by [`PrefabLoader`] components of the scene
+pub fn scene_dependencies(scene: &DynamicScene) -> Vec<String> {
+    let mut dependencies: Vec<String> = scene
+        .entities
+        .iter()
+        .flat_map(|entity| entity.components.iter())
+        .filter(|component| is_type::<PrefabLoader>(component.as_reflect()))
+        .filter_map(|component| PrefabLoader::from_reflect(component.as_reflect()))
+        .map(|loader| loader.path)
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+    dependencies
+}
+
+/// Asset files which are loaded by components of the scene, except prefab files.
+/// Labels like `#Mesh0/Primitive0` are removed, so each file is listed once
+pub fn scene_asset_dependencies(scene: &DynamicScene) -> Vec<String> {
+    let mut dependencies: Vec<String> = scene
+        .entities
+        .iter()
+        .flat_map(|entity| entity.components.iter())
+        .flat_map(|component| component_files(component.as_reflect()))
+        .filter(|(component, _)| *component != PrefabLoader::short_type_path())
+        .map(|(_, path)| normalize_prefab_path(asset_file(&path)))
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+    dependencies
+}
+
+/// Files used by the component of scene together with the name of component.
+/// Prefab files of [`PrefabLoader`] are included, empty paths are skipped
+pub fn component_files(component: &dyn Reflect) -> Vec<(&'static str, String)> {
+    let mut files = vec![];
+    if is_type::<MaterialPrefab>(component) {
+        if let Some(material) = MaterialPrefab::from_reflect(component) {
+            for texture in [
+                material.base_color_texture,
+                material.emissive_texture,
+                material.metallic_roughness_texture,
+                material.normal_map_texture,
+                material.occlusion_texture,
+                material.depth_map,
+            ] {
+                files.push((MaterialPrefab::short_type_path(), texture));
+            }
+        }
+    } else if is_type::<GltfPrefab>(component) {
+        if let Some(gltf) = GltfPrefab::from_reflect(component) {
+            files.push((GltfPrefab::short_type_path(), gltf.path));
+        }
+    } else if is_type::<PrefabLoader>(component) {
+        if let Some(loader) = PrefabLoader::from_reflect(component) {
+            files.push((PrefabLoader::short_type_path(), loader.path));
+        }
+    } else if is_type::<AssetMesh>(component) {
+        if let Some(mesh) = AssetMesh::from_reflect(component) {
+            files.push((AssetMesh::short_type_path(), mesh.path));
+        }
+    }
+    files.retain(|(_, path)| !path.is_empty());
+    files
+}
+
+/// File part of asset path. Label selects a part of the file, like a mesh of gltf scene
+pub fn asset_file(path: &str) -> &str {
+    path.split('#').next().unwrap_or_default()
+}
+
+/// Is value of scene the type. Components of deserialized scenes are dynamic, so they can't be downcasted
+pub(crate) fn is_type<T: TypePath>(value: &dyn Reflect) -> bool {
+    value
+        .get_represented_type_info()
+        .is_some_and(|info| info.type_path() == T::type_path())
+}
+
+/// Prefab files used by the content of scene or prefab variant file
+pub fn read_prefab_dependencies(
+    path: &str,
+    bytes: &[u8],
+    registry: &TypeRegistry,
+) -> Result<Vec<String>, String> {
+    if is_prefab_variant(path) {
+        let variant: PrefabVariant = ron::de::from_bytes(bytes).map_err(|err| err.to_string())?;
+        Ok(vec![variant.base])
+    } else {
+        let scene = SceneFormat::from_path(path).deserialize(bytes, registry)?;
+        Ok(scene_dependencies(&scene))
+    }
+}
+
+fn update_prefab_dependencies(
+    mut graph: ResMut<PrefabDependencyGraph>,
+    mut scene_events: EventReader<AssetEvent<DynamicScene>>,
+    mut variant_events: EventReader<AssetEvent<PrefabVariant>>,
+    scenes: Res<Assets<DynamicScene>>,
+    variants: Res<Assets<PrefabVariant>>,
+    assets: Res<AssetServer>,
+) {
+    for event in scene_events.read() {
+        if let AssetEvent::Added { id } | AssetEvent::Modified { id } = event {
+            //Scenes from memory cache have no path
+            if let (Some(path), Some(scene)) = (assets.get_path(*id), scenes.get(*id)) {
+                graph.set_dependencies(&path.path().to_string_lossy(), scene_dependencies(scene));
+            }
+        }
+    }
+    for event in variant_events.read() {
+        if let AssetEvent::Added { id } | AssetEvent::Modified { id } = event {
+            if let (Some(path), Some(variant)) = (assets.get_path(*id), variants.get(*id)) {
+                graph.set_dependencies(&path.path().to_string_lossy(), [variant.base.clone()]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &[&str])]) -> PrefabDependencyGraph {
+        let mut graph = PrefabDependencyGraph::default();
+        for (path, dependencies) in edges {
+            graph.set_dependencies(path, dependencies.iter().map(|d| d.to_string()));
+        }
+        graph
+    }
+
+    #[test]
+    fn users_are_found_through_other_prefabs() {
+        let graph = graph(&[
+            ("level.scn.ron", &["house.scn.ron", "tree.scn.ron"]),
+            ("house.scn.ron", &["door.scn.ron"]),
+            ("red_house.variant.ron", &["house.scn.ron"]),
+            ("tree.scn.ron", &[]),
+        ]);
+
+        assert_eq!(
+            graph.users("door.scn.ron"),
+            vec!["house.scn.ron", "level.scn.ron", "red_house.variant.ron"]
+        );
+        assert!(graph.users("level.scn.ron").is_empty());
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let graph = graph(&[
+            ("a.scn.ron", &["b.scn.ron"]),
+            ("b.scn.ron", &["c.scn.ron", "./a.scn.ron"]),
+            ("c.scn.ron", &[]),
+        ]);
+
+        assert_eq!(
+            graph.find_cycle("a.scn.ron"),
+            Some(vec![
+                "a.scn.ron".to_string(),
+                "b.scn.ron".to_string(),
+                "a.scn.ron".to_string()
+            ])
+        );
+        assert_eq!(graph.find_cycle("c.scn.ron"), None);
+        assert!(graph.find_cycle_with("a.scn.ron", "c.scn.ron").is_none());
+        assert_eq!(
+            graph.find_cycle_with("c.scn.ron", "b.scn.ron"),
+            Some(vec![
+                "c.scn.ron".to_string(),
+                "b.scn.ron".to_string(),
+                "c.scn.ron".to_string()
+            ])
+        );
+        assert!(graph.find_cycle_with("c.scn.ron", "c.scn.ron").is_some());
+    }
+
+    #[test]
+    fn scene_dependencies_are_read_from_loaders() {
+        let mut app = App::new();
+        app.register_type::<PrefabLoader>();
+        app.world.spawn(PrefabLoader {
+            path: "house.scn.ron".to_string(),
+        });
+        app.world.spawn(PrefabLoader {
+            path: "house.scn.ron".to_string(),
+        });
+        app.world.spawn(Name::new("not a prefab"));
+
+        let scene = DynamicScene::from_world(&app.world);
+        let registry = app.world.resource::<AppTypeRegistry>().read();
+        let text = scene
+            .serialize_ron(&app.world.resource::<AppTypeRegistry>().0)
+            .unwrap();
+
+        assert_eq!(scene_dependencies(&scene), vec!["house.scn.ron"]);
+        assert_eq!(
+            read_prefab_dependencies("level.scn.ron", text.as_bytes(), &registry),
+            Ok(vec!["house.scn.ron".to_string()])
+        );
+    }
+
+    #[test]
+    fn asset_dependencies_are_files_without_labels() {
+        let mut app = App::new();
+        app.register_type::<PrefabLoader>()
+            .register_type::<AssetMesh>()
+            .register_type::<GltfPrefab>();
+        app.world.spawn(PrefabLoader {
+            path: "house.scn.ron".to_string(),
+        });
+        app.world.spawn(AssetMesh {
+            path: "models/tree.glb#Mesh0/Primitive0".to_string(),
+        });
+        app.world.spawn(AssetMesh {
+            path: "./models/tree.glb#Mesh1/Primitive0".to_string(),
+        });
+        app.world.spawn(GltfPrefab {
+            path: "models/rock.gltf".to_string(),
+            scene: "Scene0".to_string(),
+        });
+        app.world.spawn(AssetMesh::default());
+
+        let scene = DynamicScene::from_world(&app.world);
+        assert_eq!(
+            scene_asset_dependencies(&scene),
+            vec!["models/rock.gltf", "models/tree.glb"]
+        );
+    }
+}
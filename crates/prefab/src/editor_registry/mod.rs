@@ -135,6 +135,8 @@ pub struct EditorRegistry {
     pub remove_components: HashMap<TypeId, RemoveComponent>,
     pub send_events: Vec<SendEvent>,
     pub silent: HashSet<TypeId>, //skip in inspector ui
+    /// Resources which are saved with scene
+    pub scene_resources: HashSet<TypeId>,
 }
 
 impl EditorRegistry {
@@ -203,6 +205,17 @@ impl EditorRegistry {
         }
     }
 
+    /// Register new resource, which will be saved with scene and restored on scene load
+    pub fn scene_resource_register<T: Resource + Reflect + GetTypeRegistration>(&mut self) {
+        self.scene_resources
+            .insert(T::get_type_registration().type_id());
+    }
+
+    /// Is resource saved with scene
+    pub fn is_scene_resource(&self, id: &TypeId) -> bool {
+        self.scene_resources.contains(id)
+    }
+
     /// Register new event, which will be shown in editor UI and can be sent
     pub fn event_register<
         T: Event + Default + Resource + Reflect + Send + Clone + 'static + GetTypeRegistration,
@@ -260,6 +273,11 @@ pub trait EditorRegistryExt {
             + GetTypeRegistration
             + TypePath;
 
+    /// register new resource, which will be saved with scene and restored when scene is loaded
+    fn editor_scene_resource<T: Resource + Reflect + FromReflect + GetTypeRegistration>(
+        &mut self,
+    ) -> &mut Self;
+
     /// register new event in editor UI
     fn editor_registry_event<
         T: Event + Default + Resource + Reflect + Send + Clone + 'static + GetTypeRegistration,
@@ -347,6 +365,17 @@ impl EditorRegistryExt for App {
         self
     }
 
+    fn editor_scene_resource<T: Resource + Reflect + FromReflect + GetTypeRegistration>(
+        &mut self,
+    ) -> &mut Self {
+        self.world
+            .resource_mut::<EditorRegistry>()
+            .scene_resource_register::<T>();
+        self.register_type::<T>();
+        self.auto_undo_resource::<T>();
+        self
+    }
+
     fn editor_registry_event<
         T: Event + Default + Resource + Reflect + Send + Clone + 'static + GetTypeRegistration,
     >(
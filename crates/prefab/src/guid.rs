@@ -0,0 +1,256 @@
+use bevy::{ecs::reflect::ReflectComponent, prelude::*, utils::HashMap, utils::Uuid};
+use space_shared::PrefabMarker;
+
+use crate::{
+    component::{EntityLink, SceneAutoChild},
+    editor_registry::{EditorRegistry, EditorRegistryExt},
+    save::visit_reflect_mut,
+    PrefabSet,
+};
+
+/// Gives persistent ids to scene entities and restores [`EntityLink`]s by them after load
+pub struct PrefabGuidPlugin;
+
+impl Plugin for PrefabGuidPlugin {
+    fn build(&self, app: &mut App) {
+        app.editor_silent_registry::<PrefabGuid>();
+        app.register_type::<Option<PrefabGuid>>();
+        app.add_systems(
+            Update,
+            (
+                assign_prefab_guids.in_set(PrefabSet::Relation),
+                resolve_entity_links
+                    .after(PrefabSet::PrefabChangeApply)
+                    .run_if(prefab_guids_added),
+            ),
+        );
+    }
+}
+
+/// Persistent id of a scene entity. [`Entity`] ids change on every load,
+/// while this id is saved with the scene and stays the same. Cloned entities get a new id
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component, Default)]
+pub struct PrefabGuid(pub Uuid);
+
+impl PrefabGuid {
+    /// Create new random id
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for PrefabGuid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type SceneEntityFilter = (With<PrefabMarker>, Without<SceneAutoChild>);
+
+fn assign_prefab_guids(
+    mut commands: Commands,
+    query: Query<Entity, (SceneEntityFilter, Without<PrefabGuid>)>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(PrefabGuid::new());
+    }
+}
+
+fn prefab_guids_added(query: Query<(), Added<PrefabGuid>>) -> bool {
+    !query.is_empty()
+}
+
+/// Links of new entities are restored by guid. Links of other entities are restored
+/// only when their entity does not exist, for example if it was loaded later
+fn resolve_entity_links(world: &mut World, mut new_entities: Local<Vec<Entity>>) {
+    let mut added = world.query_filtered::<Entity, (SceneEntityFilter, Added<PrefabGuid>)>();
+    new_entities.clear();
+    new_entities.extend(added.iter(world));
+
+    let mut query = world.query_filtered::<(Entity, &PrefabGuid), SceneEntityFilter>();
+    let guids: HashMap<Entity, PrefabGuid> = query
+        .iter(world)
+        .map(|(entity, guid)| (entity, *guid))
+        .collect();
+    let entities: HashMap<PrefabGuid, Entity> = guids
+        .iter()
+        .map(|(entity, guid)| (*guid, *entity))
+        .collect();
+
+    let mut scene_entities: Vec<Entity> = guids.keys().copied().collect();
+    scene_entities.sort();
+    for_each_entity_link(world, &scene_entities, |entity, link| {
+        let Some(target) = link.guid.and_then(|guid| entities.get(&guid)) else {
+            return;
+        };
+        if new_entities.contains(&entity) || !guids.contains_key(&link.entity) {
+            link.entity = *target;
+        }
+    });
+}
+
+/// Give ids to scene entities and store ids of linked entities in their [`EntityLink`]s.
+/// Called before scene is saved
+pub fn prepare_prefab_guids(world: &mut World) {
+    let mut without_guid =
+        world.query_filtered::<Entity, (SceneEntityFilter, Without<PrefabGuid>)>();
+    let new_entities: Vec<Entity> = without_guid.iter(world).collect();
+    for entity in new_entities {
+        world.entity_mut(entity).insert(PrefabGuid::new());
+    }
+
+    let mut query = world.query_filtered::<(Entity, &PrefabGuid), SceneEntityFilter>();
+    let guids: HashMap<Entity, PrefabGuid> = query
+        .iter(world)
+        .map(|(entity, guid)| (entity, *guid))
+        .collect();
+    let scene_entities: Vec<Entity> = guids.keys().copied().collect();
+    for_each_entity_link(world, &scene_entities, |_, link| {
+        link.guid = guids.get(&link.entity).copied();
+    });
+}
+
+/// Visit all [`EntityLink`]s inside of saved components of the entities
+fn for_each_entity_link(
+    world: &mut World,
+    entities: &[Entity],
+    mut visitor: impl FnMut(Entity, &mut EntityLink),
+) {
+    let registry = world.resource::<EditorRegistry>().registry.clone();
+    let components: Vec<ReflectComponent> = registry
+        .read()
+        .iter()
+        .filter_map(|registration| registration.data::<ReflectComponent>().cloned())
+        .collect();
+
+    for entity in entities.iter().copied() {
+        let Some(mut entity_mut) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        for component in components.iter() {
+            let Some(mut value) = component.reflect_mut(&mut entity_mut) else {
+                continue;
+            };
+            //Links are updated without change detection to keep undo history clean
+            visit_reflect_mut(value.bypass_change_detection(), &mut |value| {
+                let Some(link) = value.downcast_mut::<EntityLink>() else {
+                    return false;
+                };
+                visitor(entity, link);
+                true
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        editor_registry::EditorRegistryPlugin,
+        save::{extract_prefab_scene, ChildrenPrefab},
+    };
+    use bevy::scene::serde::SceneDeserializer;
+    use serde::de::DeserializeSeed;
+
+    #[derive(Component, Reflect, Clone, Default)]
+    #[reflect(Component, Default)]
+    struct Follow {
+        target: EntityLink,
+    }
+
+    fn guid_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            EditorRegistryPlugin {},
+            PrefabGuidPlugin,
+        ))
+        .register_type::<EntityLink>()
+        .register_type::<Vec<Entity>>()
+        .editor_registry::<ChildrenPrefab>()
+        .editor_registry::<PrefabMarker>()
+        .editor_registry::<Name>()
+        .editor_registry::<Follow>();
+        app
+    }
+
+    #[test]
+    fn links_are_restored_by_guid_after_load() {
+        let mut app = guid_app();
+        let target = app.world.spawn((PrefabMarker, Name::new("target"))).id();
+        let follower = app
+            .world
+            .spawn((
+                PrefabMarker,
+                Follow {
+                    target: EntityLink {
+                        entity: target,
+                        guid: None,
+                    },
+                },
+            ))
+            .id();
+        app.update();
+        let target_guid = *app.world.get::<PrefabGuid>(target).unwrap();
+
+        prepare_prefab_guids(&mut app.world);
+        assert_eq!(
+            app.world.get::<Follow>(follower).unwrap().target.guid,
+            Some(target_guid)
+        );
+
+        let scene = extract_prefab_scene(&app.world, [target, follower].into_iter());
+        let text = scene
+            .serialize_ron(app.world.resource::<AppTypeRegistry>())
+            .unwrap();
+
+        //Load the scene in other world, where entity ids are different
+        let mut loaded = guid_app();
+        for _ in 0..10 {
+            loaded.world.spawn_empty();
+        }
+        let scene = {
+            let registry = loaded.world.resource::<AppTypeRegistry>().read();
+            let mut deserializer = ron::de::Deserializer::from_str(&text).unwrap();
+            SceneDeserializer {
+                type_registry: &registry,
+            }
+            .deserialize(&mut deserializer)
+            .unwrap()
+        };
+        //Link points to unrelated entity, as if the component did not implement MapEntities
+        let mut entity_map = Default::default();
+        scene
+            .write_to_world(&mut loaded.world, &mut entity_map)
+            .unwrap();
+        loaded.update();
+
+        let mut query = loaded.world.query::<(Entity, &PrefabGuid)>();
+        let (loaded_target, _) = query
+            .iter(&loaded.world)
+            .find(|(_, guid)| **guid == target_guid)
+            .unwrap();
+        let mut followers = loaded.world.query::<&Follow>();
+        let link = &followers.single(&loaded.world).target;
+        assert_eq!(link.entity, loaded_target);
+        assert_eq!(link.guid, Some(target_guid));
+    }
+
+    #[test]
+    fn guids_are_assigned_to_scene_entities() {
+        let mut app = guid_app();
+        let entity = app.world.spawn(PrefabMarker).id();
+        let auto_child = app.world.spawn((PrefabMarker, SceneAutoChild)).id();
+        app.update();
+
+        let guid = *app.world.get::<PrefabGuid>(entity).unwrap();
+        assert!(app.world.get::<PrefabGuid>(auto_child).is_none());
+
+        app.update();
+        assert_eq!(*app.world.get::<PrefabGuid>(entity).unwrap(), guid);
+    }
+}
@@ -7,14 +7,24 @@ compile_error!("feature \"f32\" and feature \"f64\" cannot be enabled at the sam
 
 /// Contains all component for prefab logic
 pub mod component;
+/// Contains dependency graph of prefab files
+pub mod dependencies;
+/// Contains persistent ids of scene entities
+pub mod guid;
 /// Contains systems for loading prefab from file
 pub mod load;
+/// Contains per instance overrides of prefabs
+pub mod overrides;
 /// Module contains all prefab plugin extensions
 pub mod plugins;
 /// Contains systems for saving prefab
 pub mod save;
+/// Contains RON and binary encodings of scene files
+pub mod scene_format;
 /// Contains systems for spawning prefabs
 pub mod spawn_system;
+/// Contains headless validation of scene files
+pub mod validation;
 
 /// Module for saving subscene state (like edit gltf entities)
 pub mod sub_scene;
@@ -33,11 +43,16 @@ pub mod ext {
 /// All useful structure from this crate
 pub mod prelude {
     pub use crate::component::*;
+    pub use crate::dependencies::PrefabDependencyGraph;
     pub use crate::editor_registry::*;
+    pub use crate::guid::PrefabGuid;
     pub use crate::load::PrefabBundle;
+    pub use crate::overrides::{PrefabInstanceChild, PrefabOverride, PrefabOverrides};
     pub use crate::plugins::*;
     pub use crate::save::*;
+    pub use crate::scene_format::SceneFormat;
     pub use crate::sub_scene::*;
+    pub use crate::validation::{SceneIssue, SceneReport, SceneValidator};
     pub use crate::PrefabSet;
     pub use space_shared::PrefabMarker;
 }
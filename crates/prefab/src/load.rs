@@ -1,8 +1,22 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    scene::{DynamicEntity, SceneInstance, SceneInstanceReady},
+    utils::{BoxedFuture, HashMap, HashSet},
+};
 use bevy_scene_hook::SceneHook;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "editor")]
+use space_shared::toast::ToastMessage;
 use space_shared::PrefabMarker;
+use space_undo::{OneFrameUndoIgnore, UndoIgnoreResources, UndoIgnoreStorage, UndoSet};
 
-use crate::prelude::EditorRegistryExt;
+use crate::{
+    dependencies::{normalize_prefab_path, PrefabDependencyGraph, PrefabDependencyPlugin},
+    overrides::PrefabOverride,
+    prelude::EditorRegistryExt,
+    scene_format::BinarySceneLoader,
+};
 
 use super::save::ChildrenPrefab;
 
@@ -45,10 +59,25 @@ impl Plugin for LoadPlugin {
     fn build(&self, app: &mut App) {
         app.editor_registry::<PrefabLoader>();
 
+        app.init_asset::<PrefabVariant>()
+            .register_asset_loader(PrefabVariantLoader);
+        app.init_asset_loader::<BinarySceneLoader>();
+        app.init_resource::<NestedSceneCopies>();
+        app.add_plugins(PrefabDependencyPlugin);
+
+        #[cfg(feature = "editor")]
+        app.add_event::<ToastMessage>();
+
         app.add_systems(
             Update,
             load_prefab.after(bevy_scene_hook::Systems::SceneHookRunner),
         );
+        app.add_systems(Update, resolve_prefab_variants.after(load_prefab));
+        app.add_systems(
+            Update,
+            spawn_nested_prefab_scenes.after(resolve_prefab_variants),
+        );
+        app.add_systems(Update, hot_reload_prefabs.before(load_prefab));
         app.add_systems(
             Update,
             conflict_resolve
@@ -56,6 +85,10 @@ impl Plugin for LoadPlugin {
                 .before(load_prefab),
         );
         app.add_systems(Update, auto_children);
+        app.add_systems(
+            PostUpdate,
+            ignore_restored_scene_resources.before(UndoSet::PerType),
+        );
     }
 }
 
@@ -66,6 +99,85 @@ pub struct PrefabLoader {
     pub path: String,
 }
 
+/// Extension of prefab variant files
+pub const PREFAB_VARIANT_EXTENSION: &str = "variant.ron";
+
+/// Is file a prefab variant or a usual scene file
+pub fn is_prefab_variant(path: &str) -> bool {
+    path.ends_with(&format!(".{}", PREFAB_VARIANT_EXTENSION))
+}
+
+/// Prefab which is defined as changes of another prefab.
+/// Base can be a scene file or another variant
+#[derive(Asset, TypePath, Serialize, Deserialize, Default, Clone, Debug)]
+pub struct PrefabVariant {
+    /// Path to base prefab
+    pub base: String,
+    /// Changes of base prefab entities
+    pub overrides: Vec<PrefabOverride>,
+}
+
+impl PrefabVariant {
+    /// Add overrides to variant. Existing overrides of the same fields are replaced
+    pub fn merge(&mut self, overrides: impl IntoIterator<Item = PrefabOverride>) {
+        for prefab_override in overrides {
+            self.overrides.retain(|o| {
+                !(o.entity == prefab_override.entity
+                    && o.component == prefab_override.component
+                    && o.field == prefab_override.field)
+            });
+            self.overrides.push(prefab_override);
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct PrefabVariantLoader;
+
+impl AssetLoader for PrefabVariantLoader {
+    type Asset = PrefabVariant;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<PrefabVariant, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            ron::de::from_bytes(&bytes)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[PREFAB_VARIANT_EXTENSION]
+    }
+}
+
+/// Variant files of prefab instance which are being loaded.
+/// Each next variant is the base of previous one
+#[derive(Component)]
+pub struct PrefabVariantChain {
+    variants: Vec<(String, Handle<PrefabVariant>)>,
+}
+
+/// Overrides of all variants in chain. They are applied to the spawned scene before overrides of the instance
+#[derive(Component, Default, Clone)]
+pub struct PrefabVariantOverrides(pub Vec<PrefabOverride>);
+
+/// Variant files of the spawned scene. Keeps them loaded to respawn the scene when they are changed
+#[derive(Component, Default, Clone)]
+pub struct PrefabVariantHandles(pub Vec<Handle<PrefabVariant>>);
+
 /// System responsible for loading prefabs
 fn load_prefab(
     mut commands: Commands,
@@ -80,7 +192,14 @@ fn load_prefab(
         Changed<PrefabLoader>,
     >,
     auto_children: Query<Entity, With<PrefabAutoChild>>,
+    scene_instances: Query<&SceneInstance>,
+    mut scene_spawner: ResMut<SceneSpawner>,
+    parents: Query<&Parent>,
+    loaders: Query<&PrefabLoader>,
+    nested: Query<(), NestedInstanceFilter>,
+    graph: Res<PrefabDependencyGraph>,
     assets: ResMut<AssetServer>,
+    #[cfg(feature = "editor")] mut toast: EventWriter<ToastMessage>,
 ) {
     for (e, l, children, tr, vis) in query.iter() {
         if tr.is_none() {
@@ -96,23 +215,306 @@ fn load_prefab(
         if let Some(children) = children {
             for child in children {
                 if auto_children.contains(*child) {
+                    //Otherwise scene spawner tries to update despawned entities on hot reload
+                    if let Ok(instance) = scene_instances.get(*child) {
+                        scene_spawner.despawn_instance(**instance);
+                    }
                     commands.entity(*child).despawn_recursive();
                 }
             }
             commands.entity(e).clear_children();
         }
 
-        let scene: Handle<DynamicScene> = assets.load(&l.path);
+        if let Some(cycle) = find_instance_cycle(e, &l.path, &parents, &loaders)
+            .or_else(|| graph.find_cycle(&l.path))
+        {
+            let msg = format!(
+                "Prefab {} is not loaded because it includes itself: {}",
+                l.path,
+                cycle.join(" -> ")
+            );
+            #[cfg(feature = "editor")]
+            toast.send(ToastMessage::new(
+                &msg,
+                space_shared::toast::ToastKind::Error,
+            ));
+            error!(msg);
+            commands.entity(e).remove::<PrefabVariantChain>();
+            continue;
+        }
 
-        let id = commands
-            .spawn(DynamicSceneBundle { scene, ..default() })
-            .insert(SceneHook::new(move |_e, cmd| {
-                cmd.insert(PrefabAutoChild);
-            }))
-            .insert(PrefabAutoChild)
-            .id();
+        if is_prefab_variant(&l.path) {
+            //Scene will be spawned after all bases are loaded
+            commands.entity(e).insert(PrefabVariantChain {
+                variants: vec![(l.path.clone(), assets.load(&l.path))],
+            });
+        } else {
+            commands.entity(e).remove::<PrefabVariantChain>();
+            spawn_prefab_scene(
+                &mut commands,
+                e,
+                assets.load(&l.path),
+                vec![],
+                vec![],
+                nested.contains(e),
+            );
+        }
+    }
+}
+
+/// Chain of prefab instances from the outer instance of the same file to the loaded one
+fn find_instance_cycle(
+    entity: Entity,
+    path: &str,
+    parents: &Query<&Parent>,
+    loaders: &Query<&PrefabLoader>,
+) -> Option<Vec<String>> {
+    let path = normalize_prefab_path(path);
+    let mut chain = vec![path.clone()];
+    for ancestor in parents.iter_ancestors(entity) {
+        let Ok(loader) = loaders.get(ancestor) else {
+            continue;
+        };
+        let loader_path = normalize_prefab_path(&loader.path);
+        chain.push(loader_path.clone());
+        if loader_path == path {
+            chain.reverse();
+            return Some(chain);
+        }
+    }
+    None
+}
 
-        commands.entity(e).push_children(&[id]);
+/// Prefab instances which are entities of another scene.
+/// Only instances which are not a part of another scene restore scene resources
+type NestedInstanceFilter = Or<(With<PrefabMarker>, With<PrefabAutoChild>)>;
+
+fn spawn_prefab_scene(
+    commands: &mut Commands,
+    instance: Entity,
+    scene: Handle<DynamicScene>,
+    variant_overrides: Vec<PrefabOverride>,
+    variants: Vec<Handle<PrefabVariant>>,
+    nested: bool,
+) {
+    let mut root = if nested {
+        //Scene is spawned by spawn_nested_prefab_scenes when it is loaded
+        commands.spawn((
+            NestedPrefabScene(scene),
+            TransformBundle::default(),
+            VisibilityBundle::default(),
+        ))
+    } else {
+        commands.spawn(DynamicSceneBundle { scene, ..default() })
+    };
+    let id = root
+        .insert(SceneHook::new(move |_e, cmd| {
+            cmd.insert(PrefabAutoChild);
+        }))
+        .insert(PrefabAutoChild)
+        .insert(PrefabVariantOverrides(variant_overrides))
+        .insert(PrefabVariantHandles(variants))
+        .id();
+
+    commands.entity(instance).push_children(&[id]);
+}
+
+/// Scene file of prefab instance which is an entity of another scene.
+/// Scene resources of such instances are not restored, so the spawned scene can be a copy without them
+#[derive(Component)]
+pub struct NestedPrefabScene(pub Handle<DynamicScene>);
+
+/// Copies of scenes without resources, which are spawned for nested prefab instances
+#[derive(Resource, Default)]
+pub struct NestedSceneCopies {
+    copies: HashMap<AssetId<DynamicScene>, Handle<DynamicScene>>,
+}
+
+fn spawn_nested_prefab_scenes(
+    mut commands: Commands,
+    query: Query<(Entity, &NestedPrefabScene), Without<Handle<DynamicScene>>>,
+    mut scenes: ResMut<Assets<DynamicScene>>,
+    mut copies: ResMut<NestedSceneCopies>,
+) {
+    for (e, nested) in query.iter() {
+        let Some(scene) = scenes.get(&nested.0) else {
+            continue;
+        };
+        let handle = if scene.resources.is_empty() {
+            nested.0.clone()
+        } else if let Some(copy) = copies.copies.get(&nested.0.id()) {
+            copy.clone()
+        } else {
+            let copy = scene_without_resources(scene);
+            let copy = scenes.add(copy);
+            copies.copies.insert(nested.0.id(), copy.clone());
+            copy
+        };
+        commands.entity(e).insert(handle);
+    }
+}
+
+/// Copy of scene entities
+pub fn scene_without_resources(scene: &DynamicScene) -> DynamicScene {
+    DynamicScene {
+        resources: vec![],
+        entities: scene
+            .entities
+            .iter()
+            .map(|entity| DynamicEntity {
+                entity: entity.entity,
+                components: entity
+                    .components
+                    .iter()
+                    .map(|component| component.clone_value())
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Load bases of prefab variants one by one and spawn the scene at the end of chain
+fn resolve_prefab_variants(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut PrefabVariantChain)>,
+    nested: Query<(), NestedInstanceFilter>,
+    variants: Res<Assets<PrefabVariant>>,
+    assets: Res<AssetServer>,
+) {
+    for (e, mut chain) in query.iter_mut() {
+        let Some((path, handle)) = chain.variants.last() else {
+            commands.entity(e).remove::<PrefabVariantChain>();
+            continue;
+        };
+        if assets.load_state(handle) == LoadState::Failed {
+            error!("Failed to load prefab variant {}", path);
+            commands.entity(e).remove::<PrefabVariantChain>();
+            continue;
+        }
+        let Some(variant) = variants.get(handle) else {
+            continue;
+        };
+
+        if is_prefab_variant(&variant.base) {
+            if chain.variants.iter().any(|(path, _)| *path == variant.base) {
+                error!("Prefab variant {} is based on itself", variant.base);
+                commands.entity(e).remove::<PrefabVariantChain>();
+                continue;
+            }
+            let base = variant.base.clone();
+            let base_handle = assets.load(&base);
+            chain.variants.push((base, base_handle));
+        } else {
+            // Overrides of derived variants go last to replace overrides of their bases
+            let overrides = chain
+                .variants
+                .iter()
+                .rev()
+                .filter_map(|(_, handle)| variants.get(handle))
+                .flat_map(|variant| variant.overrides.iter().cloned())
+                .collect();
+            let handles = chain
+                .variants
+                .iter()
+                .map(|(_, handle)| handle.clone())
+                .collect();
+            spawn_prefab_scene(
+                &mut commands,
+                e,
+                assets.load(&variant.base),
+                overrides,
+                handles,
+                nested.contains(e),
+            );
+            commands.entity(e).remove::<PrefabVariantChain>();
+        }
+    }
+}
+
+/// Scene resources restored by spawned prefab instances are not an undoable change
+fn ignore_restored_scene_resources(
+    mut events: EventReader<SceneInstanceReady>,
+    roots: Query<&Handle<DynamicScene>, With<PrefabAutoChild>>,
+    scenes: Res<Assets<DynamicScene>>,
+    ignore_resources: Option<ResMut<UndoIgnoreResources>>,
+) {
+    let Some(mut ignore_resources) = ignore_resources else {
+        events.clear();
+        return;
+    };
+    for event in events.read() {
+        if let Some(scene) = roots
+            .get(event.parent)
+            .ok()
+            .and_then(|handle| scenes.get(handle))
+        {
+            ignore_resources.ignore_scene(scene);
+        }
+    }
+}
+
+/// Respawn prefab instances when their scene or variant files are changed.
+/// Instance entity is kept, so its transform, name and overrides stay the same
+fn hot_reload_prefabs(
+    mut scene_events: EventReader<AssetEvent<DynamicScene>>,
+    mut variant_events: EventReader<AssetEvent<PrefabVariant>>,
+    roots: Query<
+        (
+            &Parent,
+            Option<&Handle<DynamicScene>>,
+            Option<&NestedPrefabScene>,
+            Option<&PrefabVariantHandles>,
+        ),
+        With<PrefabAutoChild>,
+    >,
+    mut loaders: Query<&mut PrefabLoader>,
+    mut copies: ResMut<NestedSceneCopies>,
+    mut ignore_storage: Option<ResMut<UndoIgnoreStorage>>,
+) {
+    let modified_scenes: HashSet<_> = scene_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let modified_variants: HashSet<_> = variant_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if modified_scenes.is_empty() && modified_variants.is_empty() {
+        return;
+    }
+
+    //Copies are created again from the new content
+    copies.copies.retain(|id, _| !modified_scenes.contains(id));
+
+    for (parent, scene, nested, variants) in roots.iter() {
+        //Nested instances spawn a copy of the scene file
+        let scene = nested.map(|nested| &nested.0).or(scene);
+        let modified = scene.is_some_and(|scene| modified_scenes.contains(&scene.id()))
+            || variants.is_some_and(|variants| {
+                variants
+                    .0
+                    .iter()
+                    .any(|variant| modified_variants.contains(&variant.id()))
+            });
+        if !modified {
+            continue;
+        }
+        let Ok(mut loader) = loaders.get_mut(parent.get()) else {
+            continue;
+        };
+        //Reload is not a change of PrefabLoader for undo
+        if let Some(ignore_storage) = ignore_storage.as_mut() {
+            ignore_storage
+                .storage
+                .insert(parent.get(), OneFrameUndoIgnore::default());
+        }
+        loader.set_changed();
     }
 }
 
@@ -125,7 +527,7 @@ fn conflict_resolve(
     }
 }
 
-fn auto_children(
+pub(crate) fn auto_children(
     mut commands: Commands,
     query: Query<(Entity, &ChildrenPrefab)>,
     existen_entity: Query<Entity>,
@@ -140,3 +542,266 @@ fn auto_children(
         cmds.remove::<ChildrenPrefab>();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health_override(value: &str) -> PrefabOverride {
+        PrefabOverride {
+            entity: "/Enemy".to_string(),
+            component: "game::Health".to_string(),
+            field: ".value".to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn prefab_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            crate::prelude::EditorRegistryPlugin {},
+            LoadPlugin,
+        ));
+        app
+    }
+
+    #[test]
+    fn prefab_inside_of_itself_is_not_loaded() {
+        let mut app = prefab_app();
+        let outer = app.world.spawn(PrefabBundle::new("house.scn.ron")).id();
+        app.update();
+
+        let scene_root = app.world.get::<Children>(outer).unwrap()[0];
+        let inner = app
+            .world
+            .spawn(PrefabBundle::new("./house.scn.ron"))
+            .set_parent(scene_root)
+            .id();
+        app.update();
+
+        assert!(app.world.get::<Children>(inner).is_none());
+    }
+
+    #[test]
+    fn prefab_with_cyclic_dependencies_is_not_loaded() {
+        let mut app = prefab_app();
+        let mut graph = app.world.resource_mut::<PrefabDependencyGraph>();
+        graph.set_dependencies("house.scn.ron", vec!["room.scn.ron".to_string()]);
+        graph.set_dependencies("room.scn.ron", vec!["house.scn.ron".to_string()]);
+
+        let cyclic = app.world.spawn(PrefabBundle::new("house.scn.ron")).id();
+        let usual = app.world.spawn(PrefabBundle::new("tree.scn.ron")).id();
+        app.update();
+
+        assert!(app.world.get::<Children>(cyclic).is_none());
+        assert!(app.world.get::<Children>(usual).is_some());
+    }
+
+    #[test]
+    fn modified_prefab_is_respawned() {
+        let mut app = prefab_app();
+        let instance = app
+            .world
+            .spawn(PrefabBundle::new("house.scn.ron"))
+            .insert(Transform::from_xyz(1.0, 2.0, 3.0))
+            .insert(Name::new("My house"))
+            .id();
+        app.update();
+
+        let old_root = app.world.get::<Children>(instance).unwrap()[0];
+        let id = app
+            .world
+            .get::<Handle<DynamicScene>>(old_root)
+            .unwrap()
+            .id();
+        app.world.send_event(AssetEvent::Modified { id });
+        app.update();
+
+        let children = app.world.get::<Children>(instance).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_ne!(children[0], old_root);
+        assert!(app.world.get_entity(old_root).is_none());
+        assert_eq!(
+            app.world.get::<Transform>(instance),
+            Some(&Transform::from_xyz(1.0, 2.0, 3.0))
+        );
+        assert_eq!(
+            app.world.get::<Name>(instance).unwrap().as_str(),
+            "My house"
+        );
+    }
+
+    #[test]
+    fn nested_prefab_does_not_restore_scene_resources() {
+        #[derive(Resource, Reflect, Default, PartialEq, Debug)]
+        #[reflect(Resource)]
+        struct Weather(f32);
+
+        let mut app = prefab_app();
+        app.register_type::<Weather>().insert_resource(Weather(1.0));
+        let scene = DynamicScene {
+            resources: vec![Box::new(Weather(2.0))],
+            entities: vec![],
+        };
+        let scene = app.world.resource_mut::<Assets<DynamicScene>>().add(scene);
+
+        let root = app
+            .world
+            .spawn((NestedPrefabScene(scene.clone()), PrefabAutoChild))
+            .id();
+        app.update();
+        app.update();
+
+        let spawned = app.world.get::<Handle<DynamicScene>>(root).unwrap();
+        assert_ne!(*spawned, scene);
+        let scenes = app.world.resource::<Assets<DynamicScene>>();
+        assert!(scenes.get(spawned).unwrap().resources.is_empty());
+        assert_eq!(*app.world.resource::<Weather>(), Weather(1.0));
+    }
+
+    #[test]
+    fn variant_chain_is_resolved_to_base_scene() {
+        let folder = std::env::temp_dir().join("space_prefab_variant_chain");
+        std::fs::create_dir_all(&folder).unwrap();
+        let armor_override = PrefabOverride {
+            field: ".armor".to_string(),
+            ..health_override("(5)")
+        };
+        let variants = [
+            (
+                "big.variant.ron",
+                PrefabVariant {
+                    base: "enemy.scn.ron".to_string(),
+                    overrides: vec![health_override("(10)"), armor_override.clone()],
+                },
+            ),
+            (
+                "boss.variant.ron",
+                PrefabVariant {
+                    base: "big.variant.ron".to_string(),
+                    overrides: vec![health_override("(20)")],
+                },
+            ),
+            (
+                "self.variant.ron",
+                PrefabVariant {
+                    base: "self.variant.ron".to_string(),
+                    overrides: vec![],
+                },
+            ),
+        ];
+        for (path, variant) in variants.iter() {
+            std::fs::write(folder.join(path), variant.to_ron().unwrap()).unwrap();
+        }
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: folder.display().to_string(),
+                ..default()
+            },
+            bevy::scene::ScenePlugin,
+            crate::prelude::EditorRegistryPlugin {},
+            LoadPlugin,
+        ));
+        let boss = app.world.spawn(PrefabBundle::new("boss.variant.ron")).id();
+        let looped = app.world.spawn(PrefabBundle::new("self.variant.ron")).id();
+        for _ in 0..100 {
+            app.update();
+            let resolved = |e: Entity| app.world.get::<PrefabVariantChain>(e).is_none();
+            if resolved(boss) && resolved(looped) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let _ = std::fs::remove_dir_all(&folder);
+
+        let root = app.world.get::<Children>(boss).unwrap()[0];
+        //Overrides of the derived variant are applied after overrides of its base
+        assert_eq!(
+            app.world.get::<PrefabVariantOverrides>(root).unwrap().0,
+            vec![
+                health_override("(10)"),
+                armor_override,
+                health_override("(20)")
+            ]
+        );
+        assert_eq!(
+            app.world.get::<PrefabVariantHandles>(root).unwrap().0.len(),
+            2
+        );
+        assert!(app.world.get::<Children>(looped).is_none());
+        assert!(app.world.get::<PrefabVariantChain>(looped).is_none());
+    }
+
+    #[test]
+    fn restored_scene_resources_are_not_undoable() {
+        use bevy::ecs::system::CommandQueue;
+        use space_undo::AppAutoUndo;
+
+        let mut app = prefab_app();
+        app.add_plugins(space_undo::UndoPlugin)
+            .init_resource::<AmbientLight>()
+            .register_type::<AmbientLight>()
+            .auto_undo_resource::<AmbientLight>();
+        app.update();
+        app.update();
+
+        let scene = DynamicScene {
+            resources: vec![Box::new(AmbientLight {
+                brightness: 500.0,
+                ..default()
+            })],
+            entities: vec![],
+        };
+        let scene = app.world.resource_mut::<Assets<DynamicScene>>().add(scene);
+        let instance = app.world.spawn_empty().id();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        spawn_prefab_scene(&mut commands, instance, scene, vec![], vec![], false);
+        queue.apply(&mut app.world);
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert_eq!(app.world.resource::<AmbientLight>().brightness, 500.0);
+        assert!(app
+            .world
+            .resource::<space_undo::ChangeChain>()
+            .changes
+            .is_empty());
+    }
+
+    #[test]
+    fn variant_extension() {
+        assert!(is_prefab_variant("enemies/boss.variant.ron"));
+        assert!(!is_prefab_variant("enemies/boss.scn.ron"));
+    }
+
+    #[test]
+    fn merge_replaces_same_field() {
+        let mut variant = PrefabVariant {
+            base: "enemy.scn.ron".to_string(),
+            overrides: vec![health_override("(10)")],
+        };
+        variant.merge(vec![health_override("(20)")]);
+
+        assert_eq!(variant.overrides, vec![health_override("(20)")]);
+    }
+
+    #[test]
+    fn variant_ron_roundtrip() {
+        let variant = PrefabVariant {
+            base: "enemy.scn.ron".to_string(),
+            overrides: vec![health_override("(10)")],
+        };
+        let parsed: PrefabVariant = ron::from_str(&variant.to_ron().unwrap()).unwrap();
+
+        assert_eq!(parsed.base, variant.base);
+        assert_eq!(parsed.overrides, variant.overrides);
+    }
+}
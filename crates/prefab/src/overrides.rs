@@ -0,0 +1,1087 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{ReflectSerializer, UntypedReflectDeserializer},
+        GetPath, ReflectRef, TypeRegistry,
+    },
+    utils::{HashMap, HashSet},
+};
+use bevy_scene_hook::SceneHooked;
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+use space_shared::{EditorPrefabPath, EditorState};
+use space_undo::{ChangeResult, EditorChange, NewChange, OneFrameUndoIgnore, UndoIgnoreStorage};
+
+use crate::{
+    editor_registry::{EditorRegistry, EditorRegistryExt},
+    load::{
+        is_prefab_variant, PrefabAutoChild, PrefabLoader, PrefabVariant, PrefabVariantOverrides,
+    },
+    save::{
+        apply_serialization_settings, extract_prefab_scene, spawn_scene_write, AssetFolder,
+        ChildrenPrefab, SceneSaved,
+    },
+    scene_format::SceneFormat,
+};
+
+/// Plugin for recording and applying per instance changes of prefabs
+pub struct PrefabOverridesPlugin;
+
+impl Plugin for PrefabOverridesPlugin {
+    fn build(&self, app: &mut App) {
+        app.editor_silent_registry::<PrefabOverrides>();
+        app.register_type::<PrefabOverride>();
+        app.register_type::<Vec<PrefabOverride>>();
+
+        app.add_event::<PrefabInstanceEvent>();
+        app.init_resource::<PrefabSourceWrites>();
+
+        app.add_systems(
+            Update,
+            (
+                handle_prefab_instance_events.run_if(in_state(EditorState::Editor)),
+                prepare_prefab_instances,
+                record_prefab_overrides.run_if(in_state(EditorState::Editor)),
+            )
+                .chain()
+                .after(bevy_scene_hook::Systems::SceneHookRunner)
+                .after(crate::load::auto_children),
+        );
+        app.add_systems(Update, reload_written_prefabs);
+    }
+}
+
+/// One changed field of an entity spawned by a prefab instance
+#[derive(Reflect, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[reflect(Default)]
+pub struct PrefabOverride {
+    /// Path from the instance to the changed entity.
+    /// Segments are separated by `/`, each segment is a [`Name`] or `#index` among children for unnamed entities
+    pub entity: String,
+    /// Type path of the changed component
+    pub component: String,
+    /// Reflect path of the field inside component (for example `.translation.x`).
+    /// Empty path means the whole component, which is added if entity has no such component
+    pub field: String,
+    /// Ron serialized value of the field
+    pub value: String,
+}
+
+/// Per instance changes of a [`PrefabLoader`] prefab. Stored in the parent scene
+/// and applied each time the prefab is (re)loaded
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component, Default)]
+pub struct PrefabOverrides(pub Vec<PrefabOverride>);
+
+impl PrefabOverrides {
+    /// All overrides of one component of one entity
+    pub fn get<'a>(
+        &'a self,
+        entity: &'a str,
+        component: &'a str,
+    ) -> impl Iterator<Item = &'a PrefabOverride> + 'a {
+        self.0
+            .iter()
+            .filter(move |o| o.entity == entity && o.component == component)
+    }
+
+    /// Replace overrides of one component of one entity. Returns false if nothing changed
+    pub fn replace(
+        &mut self,
+        entity: &str,
+        component: &str,
+        overrides: Vec<PrefabOverride>,
+    ) -> bool {
+        let old = self.get(entity, component).cloned().collect::<Vec<_>>();
+        if old == overrides {
+            return false;
+        }
+        self.0
+            .retain(|o| !(o.entity == entity && o.component == component));
+        self.0.extend(overrides);
+        true
+    }
+}
+
+/// Entity spawned by a prefab instance. Keeps component values loaded from the prefab file
+/// to detect which fields were changed in editor
+#[derive(Component)]
+pub struct PrefabInstanceChild {
+    /// Entity with [`PrefabLoader`] that spawned this entity
+    pub instance: Entity,
+    /// Path of this entity inside the instance, see [`PrefabOverride::entity`]
+    pub path: String,
+    baseline: HashMap<String, Box<dyn Reflect>>,
+    overridden: Vec<(String, String)>,
+}
+
+impl PrefabInstanceChild {
+    /// Is any field of the component overridden
+    pub fn is_overridden(&self, component: &str) -> bool {
+        self.overridden.iter().any(|(c, _)| c == component)
+    }
+
+    /// Reflect paths of overridden fields of the component
+    pub fn overridden_fields<'a>(&'a self, component: &'a str) -> impl Iterator<Item = &'a str> {
+        self.overridden
+            .iter()
+            .filter(move |(c, _)| c == component)
+            .map(|(_, field)| field.as_str())
+    }
+}
+
+/// Actions with prefab instances, sent from editor UI. Both actions are undoable
+#[derive(Event, Clone, Copy, Debug)]
+pub enum PrefabInstanceEvent {
+    /// Write current state of the instance to its prefab file
+    ApplyToSource(Entity),
+    /// Discard all overrides of the instance and reload it from the prefab file
+    Revert(Entity),
+}
+
+/// Change of prefab instance overrides, and of its prefab file for "Apply to source prefab"
+pub struct PrefabInstanceChange {
+    pub instance: Entity,
+    pub old_overrides: PrefabOverrides,
+    pub new_overrides: PrefabOverrides,
+    /// Old and new content of the prefab file
+    pub source: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl EditorChange for PrefabInstanceChange {
+    fn revert(
+        &self,
+        world: &mut World,
+        entity_remap: &HashMap<Entity, Entity>,
+    ) -> Result<ChangeResult, String> {
+        let instance = space_undo::get_entity_with_remap(self.instance, entity_remap);
+        let path = world
+            .get::<PrefabLoader>(instance)
+            .ok_or_else(|| format!("Prefab instance {:?} not found", instance))?
+            .path
+            .clone();
+        if let Some((old_source, _)) = &self.source {
+            write_prefab_source(world, &path, old_source);
+        }
+        world
+            .entity_mut(instance)
+            .insert(self.old_overrides.clone());
+        reload_instance(world, instance);
+        Ok(ChangeResult::Success)
+    }
+
+    fn debug_text(&self) -> String {
+        if self.source.is_some() {
+            format!("Apply prefab instance {:?} to source", self.instance)
+        } else {
+            format!("Revert prefab instance {:?}", self.instance)
+        }
+    }
+
+    fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
+        Arc::new(Self {
+            instance: self.instance,
+            old_overrides: self.new_overrides.clone(),
+            new_overrides: self.old_overrides.clone(),
+            source: self
+                .source
+                .as_ref()
+                .map(|(old, new)| (new.clone(), old.clone())),
+        })
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self
+                .source
+                .as_ref()
+                .map_or(0, |(old, new)| old.len() + new.len())
+    }
+}
+
+/// Collect all entities of a spawned prefab instance with their paths.
+/// Nested prefab instances are not entered, their children belong to them
+pub fn collect_instance_entities(world: &World, scene_root: Entity) -> Vec<(String, Entity)> {
+    let mut result = vec![];
+    let mut stack = vec![(String::new(), scene_root)];
+    while let Some((path, entity)) = stack.pop() {
+        let entity_ref = world.entity(entity);
+        if entity != scene_root && entity_ref.contains::<PrefabLoader>() {
+            result.push((path, entity));
+            continue;
+        }
+        if let Some(children) = entity_ref.get::<Children>() {
+            for (idx, child) in children.iter().enumerate() {
+                if !world.entity(*child).contains::<PrefabAutoChild>() {
+                    continue;
+                }
+                let segment = world
+                    .get::<Name>(*child)
+                    .map_or_else(|| format!("#{idx}"), |name| name.as_str().to_string());
+                stack.push((format!("{path}/{segment}"), *child));
+            }
+        }
+        result.push((path, entity));
+    }
+    result
+}
+
+/// Paths of all fields which differs in two values of the same type
+pub fn diff_fields(
+    base: &dyn Reflect,
+    current: &dyn Reflect,
+    path: &str,
+    registry: &TypeRegistry,
+    out: &mut Vec<String>,
+) {
+    match (base.reflect_ref(), current.reflect_ref()) {
+        (ReflectRef::Struct(base), ReflectRef::Struct(current)) => {
+            for idx in 0..current.field_len() {
+                let (Some(name), Some(field)) = (current.name_at(idx), current.field_at(idx))
+                else {
+                    continue;
+                };
+                let field_path = format!("{path}.{name}");
+                if let Some(base_field) = base.field(name) {
+                    diff_fields(base_field, field, &field_path, registry, out);
+                } else {
+                    out.push(field_path);
+                }
+            }
+        }
+        (ReflectRef::TupleStruct(base), ReflectRef::TupleStruct(current)) => {
+            for (idx, field) in current.iter_fields().enumerate() {
+                let field_path = format!("{path}.{idx}");
+                if let Some(base_field) = base.field(idx) {
+                    diff_fields(base_field, field, &field_path, registry, out);
+                } else {
+                    out.push(field_path);
+                }
+            }
+        }
+        _ => {
+            let equal = base.reflect_partial_eq(current).unwrap_or_else(|| {
+                //Types without PartialEq are compared by serialized value
+                serialize_value(base, registry).ok() == serialize_value(current, registry).ok()
+            });
+            if !equal {
+                out.push(path.to_string());
+            }
+        }
+    }
+}
+
+/// Serialize reflected value to ron string with type information
+pub fn serialize_value(value: &dyn Reflect, registry: &TypeRegistry) -> Result<String, String> {
+    ron::to_string(&ReflectSerializer::new(value, registry)).map_err(|err| err.to_string())
+}
+
+/// Write override value into the component of entity
+pub fn apply_override(
+    world: &mut World,
+    entity: Entity,
+    prefab_override: &PrefabOverride,
+    registry: &TypeRegistry,
+) -> Result<(), String> {
+    let reflect_component = registry
+        .get_with_type_path(&prefab_override.component)
+        .and_then(|registration| registration.data::<ReflectComponent>())
+        .ok_or_else(|| format!("{} is not registered", prefab_override.component))?;
+
+    let mut deserializer =
+        ron::de::Deserializer::from_str(&prefab_override.value).map_err(|err| err.to_string())?;
+    let value = UntypedReflectDeserializer::new(registry)
+        .deserialize(&mut deserializer)
+        .map_err(|err| err.to_string())?;
+
+    let mut entity_mut = world.entity_mut(entity);
+    if prefab_override.field.is_empty() && !reflect_component.contains(EntityRef::from(&entity_mut))
+    {
+        reflect_component.insert(&mut entity_mut, &*value, registry);
+        return Ok(());
+    }
+    let mut component = reflect_component
+        .reflect_mut(&mut entity_mut)
+        .ok_or_else(|| format!("{} not found", prefab_override.component))?;
+    let field = component
+        .as_reflect_mut()
+        .reflect_path_mut(prefab_override.field.as_str())
+        .map_err(|err| err.to_string())?;
+
+    let field_type = field.get_represented_type_info().map(|info| info.type_id());
+    let value_type = value.get_represented_type_info().map(|info| info.type_id());
+    if field_type != value_type {
+        return Err(format!(
+            "{}{} has type {}, override has type {}",
+            prefab_override.component,
+            prefab_override.field,
+            field.reflect_type_path(),
+            value.reflect_type_path()
+        ));
+    }
+    field.apply(&*value);
+    Ok(())
+}
+
+/// Snapshot of all editor components of entity
+fn take_baseline(
+    entity: EntityRef,
+    editor_registry: &EditorRegistry,
+    registry: &TypeRegistry,
+) -> HashMap<String, Box<dyn Reflect>> {
+    let mut baseline = HashMap::new();
+    for registration in editor_registry.registry.read().iter() {
+        let Some(reflect_component) = registry
+            .get(registration.type_id())
+            .and_then(|registration| registration.data::<ReflectComponent>())
+        else {
+            continue;
+        };
+        if let Some(component) = reflect_component.reflect(entity) {
+            baseline.insert(
+                registration.type_info().type_path().to_string(),
+                component.clone_value(),
+            );
+        }
+    }
+    baseline
+}
+
+/// Compare components of entity with values loaded from prefab and convert differences to overrides
+fn collect_overrides(
+    entity: EntityRef,
+    child: &PrefabInstanceChild,
+    component: &str,
+    registry: &TypeRegistry,
+) -> Vec<PrefabOverride> {
+    let Some(current) = registry
+        .get_with_type_path(component)
+        .and_then(|registration| registration.data::<ReflectComponent>())
+        .and_then(|reflect_component| reflect_component.reflect(entity))
+    else {
+        return vec![];
+    };
+
+    let mut fields = vec![];
+    if let Some(base) = child.baseline.get(component) {
+        diff_fields(base.as_ref(), current, "", registry, &mut fields);
+    } else {
+        //Component was added to the instance
+        fields.push(String::new());
+    }
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let value = current
+                .reflect_path(field.as_str())
+                .map_err(|err| err.to_string())
+                .and_then(|value| serialize_value(value, registry));
+            match value {
+                Ok(value) => Some(PrefabOverride {
+                    entity: child.path.clone(),
+                    component: component.to_string(),
+                    field,
+                    value,
+                }),
+                Err(err) => {
+                    warn!(
+                        "Failed to serialize override {}{}: {}",
+                        component, field, err
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Prefab files which are being written, mapped to their asset paths
+#[derive(Resource, Default)]
+pub struct PrefabSourceWrites {
+    files: HashMap<String, String>,
+}
+
+/// Write prefab file in the same way as scene is saved. Prefab is reloaded when the file is written
+fn write_prefab_source(world: &mut World, path: &str, data: &[u8]) {
+    let file = world.resource::<AssetFolder>().file(path);
+    world
+        .resource_mut::<PrefabSourceWrites>()
+        .files
+        .insert(file.clone(), path.to_string());
+    spawn_scene_write(world, file, data.to_vec());
+}
+
+/// Reload written prefab files, so all their instances are updated
+fn reload_written_prefabs(
+    mut events: EventReader<SceneSaved>,
+    mut writes: ResMut<PrefabSourceWrites>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in events.read() {
+        let EditorPrefabPath::File(file) = &event.path else {
+            continue;
+        };
+        let Some(path) = writes.files.remove(file) else {
+            continue;
+        };
+        if event.result.is_ok() {
+            asset_server.reload(path);
+        }
+    }
+}
+
+/// Respawn instance from its prefab file. Overrides are applied after load
+fn reload_instance(world: &mut World, instance: Entity) {
+    //Reload is not a change of PrefabLoader for undo
+    if let Some(mut ignore_storage) = world.get_resource_mut::<UndoIgnoreStorage>() {
+        ignore_storage
+            .storage
+            .insert(instance, OneFrameUndoIgnore::default());
+    }
+    if let Some(mut loader) = world.get_mut::<PrefabLoader>(instance) {
+        loader.set_changed();
+    }
+}
+
+/// Spawned scene of prefab instance. None if prefab is not loaded yet
+fn instance_scene_root(world: &World, instance: Entity) -> Option<Entity> {
+    world
+        .get::<Children>(instance)?
+        .iter()
+        .copied()
+        .find(|child| {
+            let child = world.entity(*child);
+            child.contains::<PrefabAutoChild>() && child.contains::<SceneHooked>()
+        })
+}
+
+/// Write current state of prefab instance to its prefab file
+fn apply_to_source(world: &mut World, instance: Entity) -> Result<PrefabInstanceChange, String> {
+    let path = world
+        .get::<PrefabLoader>(instance)
+        .ok_or("Entity is not a prefab instance")?
+        .path
+        .clone();
+    let root = instance_scene_root(world, instance).ok_or("Prefab is not loaded yet")?;
+    let old_overrides = world
+        .get::<PrefabOverrides>(instance)
+        .cloned()
+        .unwrap_or_default();
+
+    let file = world.resource::<AssetFolder>().file(&path);
+    let old_data = std::fs::read(&file).map_err(|err| format!("{}: {}", file, err))?;
+    let data = if is_prefab_variant(&path) {
+        //Variant stores only changes of its base
+        let mut variant = ron::de::from_bytes::<PrefabVariant>(&old_data)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        variant.merge(old_overrides.0.iter().cloned());
+        variant.to_ron()?.into_bytes()
+    } else {
+        serialize_instance_scene(world, root, SceneFormat::from_path(&path))?
+    };
+    write_prefab_source(world, &path, &data);
+
+    //Instance is equal to its source now
+    world
+        .entity_mut(instance)
+        .insert(PrefabOverrides::default());
+    reset_baselines(world, root);
+
+    Ok(PrefabInstanceChange {
+        instance,
+        old_overrides,
+        new_overrides: PrefabOverrides::default(),
+        source: Some((old_data, data)),
+    })
+}
+
+/// Serialize entities of prefab instance in the same way as scene is saved
+fn serialize_instance_scene(
+    world: &mut World,
+    root: Entity,
+    format: SceneFormat,
+) -> Result<Vec<u8>, String> {
+    //Scene root is created by PrefabLoader and is not stored in prefab file
+    let entities = collect_instance_entities(world, root)
+        .into_iter()
+        .filter(|(entity_path, _)| !entity_path.is_empty())
+        .map(|(_, entity)| entity)
+        .collect::<Vec<_>>();
+
+    // Hierarchy inside prefab file is stored in ChildrenPrefab
+    let in_prefab = entities.iter().copied().collect::<HashSet<_>>();
+    for entity in entities.iter() {
+        let children = world.get::<Children>(*entity).map(|children| {
+            children
+                .iter()
+                .filter(|child| in_prefab.contains(*child))
+                .copied()
+                .collect::<Vec<_>>()
+        });
+        if let Some(children) = children.filter(|children| !children.is_empty()) {
+            world.entity_mut(*entity).insert(ChildrenPrefab(children));
+        }
+    }
+    let mut scene = extract_prefab_scene(world, entities.iter().copied());
+    apply_serialization_settings(world, &mut scene);
+    for entity in entities.iter() {
+        world.entity_mut(*entity).remove::<ChildrenPrefab>();
+    }
+
+    format.serialize(&scene, world.resource::<AppTypeRegistry>())
+}
+
+/// Discard all overrides of prefab instance
+fn revert_instance(world: &mut World, instance: Entity) -> Result<PrefabInstanceChange, String> {
+    if world.get::<PrefabLoader>(instance).is_none() {
+        return Err("Entity is not a prefab instance".to_string());
+    }
+    let old_overrides = world
+        .get::<PrefabOverrides>(instance)
+        .cloned()
+        .unwrap_or_default();
+    world
+        .entity_mut(instance)
+        .insert(PrefabOverrides::default());
+    reload_instance(world, instance);
+
+    Ok(PrefabInstanceChange {
+        instance,
+        old_overrides,
+        new_overrides: PrefabOverrides::default(),
+        source: None,
+    })
+}
+
+/// Use current values of instance entities as values loaded from prefab
+fn reset_baselines(world: &mut World, root: Entity) {
+    let editor_registry = world.resource::<EditorRegistry>().clone();
+    let app_registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = app_registry.read();
+
+    for (_, entity) in collect_instance_entities(world, root) {
+        let baseline = take_baseline(world.entity(entity), &editor_registry, &registry);
+        if let Some(mut child) = world.get_mut::<PrefabInstanceChild>(entity) {
+            child.baseline = baseline;
+            child.overridden.clear();
+        }
+    }
+}
+
+fn handle_prefab_instance_events(world: &mut World) {
+    let events = world
+        .resource_mut::<Events<PrefabInstanceEvent>>()
+        .drain()
+        .collect::<Vec<_>>();
+    for event in events {
+        let res = match event {
+            PrefabInstanceEvent::ApplyToSource(instance) => apply_to_source(world, instance),
+            PrefabInstanceEvent::Revert(instance) => revert_instance(world, instance),
+        };
+        match res {
+            Ok(change) => {
+                world.send_event(NewChange {
+                    change: Arc::new(change),
+                });
+            }
+            Err(err) => {
+                let err = format!("Failed to {:?}: {}", event, err);
+                #[cfg(feature = "editor")]
+                world.send_event(space_shared::toast::ToastMessage::new(
+                    &err,
+                    space_shared::toast::ToastKind::Error,
+                ));
+                error!(err);
+            }
+        }
+    }
+}
+
+/// Take snapshot of freshly spawned prefab instances and apply their overrides
+fn prepare_prefab_instances(world: &mut World) {
+    let mut roots_query =
+        world.query_filtered::<(Entity, &Parent), (With<PrefabAutoChild>, Added<SceneHooked>)>();
+    let roots = roots_query
+        .iter(world)
+        .map(|(entity, parent)| (entity, parent.get()))
+        .filter(|(_, instance)| world.get::<PrefabLoader>(*instance).is_some())
+        .collect::<Vec<_>>();
+    if roots.is_empty() {
+        return;
+    }
+
+    let editor_registry = world.resource::<EditorRegistry>().clone();
+    let app_registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = app_registry.read();
+
+    for (root, instance) in roots {
+        let entities = collect_instance_entities(world, root);
+        let paths = entities.iter().cloned().collect::<HashMap<_, _>>();
+
+        //Changes of prefab variants are part of the prefab, instance overrides are made over them
+        if let Some(variant_overrides) = world.get::<PrefabVariantOverrides>(root).cloned() {
+            apply_overrides(world, &paths, &variant_overrides.0, &registry);
+        }
+
+        for (path, entity) in entities.iter() {
+            let baseline = take_baseline(world.entity(*entity), &editor_registry, &registry);
+            world.entity_mut(*entity).insert(PrefabInstanceChild {
+                instance,
+                path: path.clone(),
+                baseline,
+                overridden: vec![],
+            });
+        }
+
+        let Some(overrides) = world.get::<PrefabOverrides>(instance).cloned() else {
+            continue;
+        };
+        for (entity, prefab_override) in apply_overrides(world, &paths, &overrides.0, &registry) {
+            if let Some(mut child) = world.get_mut::<PrefabInstanceChild>(entity) {
+                child.overridden.push((
+                    prefab_override.component.clone(),
+                    prefab_override.field.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Apply overrides to entities of prefab instance. Returns successfully applied overrides
+fn apply_overrides<'a>(
+    world: &mut World,
+    paths: &HashMap<String, Entity>,
+    overrides: &'a [PrefabOverride],
+    registry: &TypeRegistry,
+) -> Vec<(Entity, &'a PrefabOverride)> {
+    let mut applied = vec![];
+    for prefab_override in overrides.iter() {
+        let Some(entity) = paths.get(&prefab_override.entity) else {
+            warn!(
+                "Prefab override target {} not found",
+                prefab_override.entity
+            );
+            continue;
+        };
+        match apply_override(world, *entity, prefab_override, registry) {
+            Ok(()) => applied.push((*entity, prefab_override)),
+            Err(err) => warn!("Failed to apply prefab override: {}", err),
+        }
+    }
+    applied
+}
+
+/// Convert changes of prefab instance children to overrides of their instance
+fn record_prefab_overrides(world: &mut World) {
+    let last_run = world.last_change_tick();
+    let this_run = world.read_change_tick();
+
+    let editor_registry = world.resource::<EditorRegistry>().clone();
+    let app_registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = app_registry.read();
+
+    let component_ids = editor_registry
+        .registry
+        .read()
+        .iter()
+        .filter_map(|registration| {
+            world
+                .components()
+                .get_id(registration.type_id())
+                .map(|id| (id, registration.type_info().type_path().to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut children_query = world.query::<(EntityRef, &PrefabInstanceChild)>();
+    let mut changes = vec![];
+    for (entity, child) in children_query.iter(world) {
+        for (id, component) in component_ids.iter() {
+            let is_changed = entity
+                .get_change_ticks_by_id(*id)
+                .is_some_and(|ticks| ticks.is_changed(last_run, this_run));
+            if is_changed {
+                changes.push((
+                    entity.id(),
+                    child.instance,
+                    child.path.clone(),
+                    component.clone(),
+                    collect_overrides(entity, child, component, &registry),
+                ));
+            }
+        }
+    }
+
+    for (entity, instance, path, component, overrides) in changes {
+        let fields = overrides
+            .iter()
+            .map(|o| (component.clone(), o.field.clone()))
+            .collect::<Vec<_>>();
+        if let Some(mut child) = world.get_mut::<PrefabInstanceChild>(entity) {
+            child.overridden.retain(|(c, _)| *c != component);
+            child.overridden.extend(fields);
+        }
+
+        let Some(mut instance) = world.get_entity_mut(instance) else {
+            continue;
+        };
+        if let Some(mut instance_overrides) = instance.get_mut::<PrefabOverrides>() {
+            // Avoid triggering change detection when nothing changed
+            if instance_overrides
+                .bypass_change_detection()
+                .replace(&path, &component, overrides)
+            {
+                instance_overrides.set_changed();
+            }
+        } else if !overrides.is_empty() {
+            instance.insert(PrefabOverrides(overrides));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::default();
+        registry.register::<Transform>();
+        registry.register::<Name>();
+        registry.register::<Vec3>();
+        registry.register::<Quat>();
+        registry.register::<f32>();
+        registry
+    }
+
+    #[test]
+    fn diff_finds_changed_fields() {
+        let registry = registry();
+        let base = Transform::from_xyz(1., 2., 3.);
+        let mut current = base;
+        current.translation.y = 5.;
+        current.scale = Vec3::splat(2.);
+
+        let mut fields = vec![];
+        diff_fields(&base, &current, "", &registry, &mut fields);
+        fields.sort();
+
+        assert_eq!(
+            fields,
+            vec![
+                ".scale.x".to_string(),
+                ".scale.y".to_string(),
+                ".scale.z".to_string(),
+                ".translation.y".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_of_equal_values_is_empty() {
+        let registry = registry();
+        let base = Transform::from_xyz(1., 2., 3.);
+
+        let mut fields = vec![];
+        diff_fields(&base, &base, "", &registry, &mut fields);
+
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn applies_serialized_override() {
+        let mut world = World::new();
+        let registry = registry();
+        let entity = world.spawn(Transform::from_xyz(1., 2., 3.)).id();
+
+        let prefab_override = PrefabOverride {
+            entity: String::new(),
+            component: Transform::type_path().to_string(),
+            field: ".translation.y".to_string(),
+            value: serialize_value(&7f32, &registry).unwrap(),
+        };
+        apply_override(&mut world, entity, &prefab_override, &registry).unwrap();
+
+        assert_eq!(
+            *world.get::<Transform>(entity).unwrap(),
+            Transform::from_xyz(1., 7., 3.)
+        );
+    }
+
+    #[test]
+    fn override_with_wrong_type_is_rejected() {
+        let mut world = World::new();
+        let registry = registry();
+        let entity = world.spawn(Transform::from_xyz(1., 2., 3.)).id();
+
+        let prefab_override = PrefabOverride {
+            entity: String::new(),
+            component: Transform::type_path().to_string(),
+            field: ".translation".to_string(),
+            value: serialize_value(&7f32, &registry).unwrap(),
+        };
+
+        assert!(apply_override(&mut world, entity, &prefab_override, &registry).is_err());
+        assert_eq!(
+            *world.get::<Transform>(entity).unwrap(),
+            Transform::from_xyz(1., 2., 3.)
+        );
+    }
+
+    #[test]
+    fn instance_paths_use_names_and_skip_nested_instances() {
+        let mut world = World::new();
+        let nested_child = world.spawn(PrefabAutoChild).id();
+        let nested = world
+            .spawn((PrefabAutoChild, PrefabLoader::default()))
+            .add_child(nested_child)
+            .id();
+        let unnamed = world.spawn(PrefabAutoChild).id();
+        let named = world
+            .spawn((PrefabAutoChild, Name::new("Body")))
+            .add_child(nested)
+            .id();
+        let foreign = world.spawn_empty().id();
+        let root = world
+            .spawn(PrefabAutoChild)
+            .push_children(&[named, unnamed, foreign])
+            .id();
+
+        let mut entities = collect_instance_entities(&world, root);
+        entities.sort();
+
+        assert_eq!(
+            entities,
+            vec![
+                (String::new(), root),
+                ("/#1".to_string(), unnamed),
+                ("/Body".to_string(), named),
+                ("/Body/#0".to_string(), nested),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_and_reapplies_overrides() {
+        let mut app = App::new();
+        app.init_resource::<EditorRegistry>()
+            .register_type::<Transform>()
+            .register_type::<f32>()
+            .add_systems(
+                Update,
+                (prepare_prefab_instances, record_prefab_overrides).chain(),
+            );
+        app.world
+            .resource::<EditorRegistry>()
+            .registry
+            .write()
+            .register::<Transform>();
+
+        let spawn_scene = |world: &mut World, instance: Entity| {
+            let body = world
+                .spawn((PrefabAutoChild, Name::new("Body"), Transform::default()))
+                .id();
+            let root = world
+                .spawn((PrefabAutoChild, SceneHooked))
+                .add_child(body)
+                .id();
+            world.entity_mut(instance).add_child(root);
+            (root, body)
+        };
+
+        let instance = app.world.spawn(PrefabLoader::default()).id();
+        let (root, body) = spawn_scene(&mut app.world, instance);
+        app.update();
+
+        app.world.get_mut::<Transform>(body).unwrap().translation.x = 3.;
+        app.update();
+
+        let overrides = app.world.get::<PrefabOverrides>(instance).unwrap();
+        assert_eq!(overrides.0.len(), 1);
+        assert_eq!(overrides.0[0].entity, "/Body");
+        assert_eq!(overrides.0[0].field, ".translation.x");
+        assert!(app
+            .world
+            .get::<PrefabInstanceChild>(body)
+            .unwrap()
+            .is_overridden(Transform::type_path()));
+
+        //Reload prefab
+        app.world.entity_mut(root).despawn_recursive();
+        let (_, body) = spawn_scene(&mut app.world, instance);
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Transform>(body).unwrap().translation,
+            Vec3::new(3., 0., 0.)
+        );
+        assert_eq!(
+            app.world.get::<PrefabOverrides>(instance).unwrap().0.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn revert_instance_is_undoable() {
+        let mut world = World::new();
+        let overrides = PrefabOverrides(vec![PrefabOverride {
+            entity: "/Body".to_string(),
+            component: Transform::type_path().to_string(),
+            field: ".translation.x".to_string(),
+            value: "{\"f32\":3.0}".to_string(),
+        }]);
+        let instance = world
+            .spawn((PrefabLoader::default(), overrides.clone()))
+            .id();
+
+        let change = revert_instance(&mut world, instance).unwrap();
+        assert!(world.get::<PrefabOverrides>(instance).unwrap().0.is_empty());
+
+        change.revert(&mut world, &HashMap::new()).unwrap();
+        assert_eq!(
+            world.get::<PrefabOverrides>(instance).unwrap().0,
+            overrides.0
+        );
+
+        change
+            .get_inverse()
+            .revert(&mut world, &HashMap::new())
+            .unwrap();
+        assert!(world.get::<PrefabOverrides>(instance).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn instance_scene_is_serialized_in_format_of_prefab_file() {
+        let mut world = World::new();
+        world.init_resource::<AppTypeRegistry>();
+        world.init_resource::<EditorRegistry>();
+        world
+            .resource::<AppTypeRegistry>()
+            .write()
+            .register::<Name>();
+        world
+            .resource::<EditorRegistry>()
+            .registry
+            .write()
+            .register::<Name>();
+        let body = world.spawn((PrefabAutoChild, Name::new("Body"))).id();
+        let root = world.spawn(PrefabAutoChild).add_child(body).id();
+
+        let format = SceneFormat::from_path("test.scn.bin");
+        let data = serialize_instance_scene(&mut world, root, format).unwrap();
+        let scene = format
+            .deserialize(&data, &world.resource::<AppTypeRegistry>().read())
+            .unwrap();
+
+        assert_eq!(format, SceneFormat::Binary);
+        assert_eq!(scene.entities.len(), 1);
+    }
+
+    #[test]
+    fn apply_to_source_is_undoable() {
+        let folder = std::env::temp_dir().join("space_prefab_apply_to_source");
+        std::fs::create_dir_all(&folder).unwrap();
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            crate::editor_registry::EditorRegistryPlugin {},
+            crate::save::SavePrefabPlugin,
+            PrefabOverridesPlugin,
+        ))
+        .insert_resource(AssetFolder(folder.display().to_string()))
+        .init_resource::<space_shared::PrefabMemoryCache>()
+        .register_type::<Transform>()
+        .register_type::<f32>();
+        #[cfg(feature = "editor")]
+        app.add_event::<space_shared::toast::ToastMessage>();
+        app.world
+            .resource::<EditorRegistry>()
+            .registry
+            .write()
+            .register::<Transform>();
+
+        let path = "box.scn.ron";
+        let body = app
+            .world
+            .spawn((PrefabAutoChild, Name::new("Body"), Transform::default()))
+            .id();
+        let root = app
+            .world
+            .spawn((PrefabAutoChild, SceneHooked))
+            .add_child(body)
+            .id();
+        let overrides = PrefabOverrides(vec![PrefabOverride {
+            entity: "/Body".to_string(),
+            component: Transform::type_path().to_string(),
+            field: ".translation.x".to_string(),
+            value: "{\"f32\":3.0}".to_string(),
+        }]);
+        let instance = app
+            .world
+            .spawn((
+                PrefabLoader {
+                    path: path.to_string(),
+                },
+                overrides.clone(),
+            ))
+            .add_child(root)
+            .id();
+        let file = app.world.resource::<AssetFolder>().file(path);
+        let old_data = serialize_instance_scene(&mut app.world, root, SceneFormat::Ron).unwrap();
+        std::fs::write(&file, &old_data).unwrap();
+
+        let wait_for_writes = |app: &mut App| {
+            for _ in 0..100 {
+                app.update();
+                if app.world.resource::<PrefabSourceWrites>().files.is_empty() {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            panic!("Prefab file was not written");
+        };
+
+        app.world.get_mut::<Transform>(body).unwrap().translation.x = 3.;
+        let change = apply_to_source(&mut app.world, instance).unwrap();
+        wait_for_writes(&mut app);
+        let new_data = std::fs::read(&file).unwrap();
+        assert_ne!(new_data, old_data);
+        assert!(app
+            .world
+            .get::<PrefabOverrides>(instance)
+            .unwrap()
+            .0
+            .is_empty());
+
+        change.revert(&mut app.world, &HashMap::new()).unwrap();
+        wait_for_writes(&mut app);
+        let reverted_data = std::fs::read(&file).unwrap();
+        let _ = std::fs::remove_dir_all(&folder);
+
+        assert_eq!(reverted_data, old_data);
+        assert_eq!(
+            app.world.get::<PrefabOverrides>(instance).unwrap().0,
+            overrides.0
+        );
+    }
+
+    #[test]
+    fn replace_keeps_other_overrides() {
+        let other = PrefabOverride {
+            entity: "/A".to_string(),
+            component: "c".to_string(),
+            field: ".x".to_string(),
+            value: "1".to_string(),
+        };
+        let old = PrefabOverride {
+            entity: "/B".to_string(),
+            ..other.clone()
+        };
+        let mut overrides = PrefabOverrides(vec![other.clone(), old.clone()]);
+
+        assert!(!overrides.replace("/B", "c", vec![old]));
+        assert!(overrides.replace("/B", "c", vec![]));
+        assert_eq!(overrides.0, vec![other]);
+    }
+}
@@ -197,6 +197,10 @@ impl Plugin for BasePrefabPlugin {
 
         app.editor_registry::<PlaymodeLight>();
 
+        //Scene resources
+        app.editor_scene_resource::<AmbientLight>();
+        app.editor_scene_resource::<ClearColor>();
+
         #[cfg(feature = "editor")]
         app.add_event::<ToastMessage>();
 
@@ -237,8 +241,10 @@ impl Plugin for BasePrefabPlugin {
         );
         app.add_systems(Update, animate_sprite);
 
+        app.add_plugins(crate::guid::PrefabGuidPlugin);
         app.add_plugins(SavePrefabPlugin);
         app.add_plugins(LoadPlugin);
+        app.add_plugins(crate::overrides::PrefabOverridesPlugin);
         app.add_plugins(crate::sub_scene::SceneUnpackPlugin);
     }
 }
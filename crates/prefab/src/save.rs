@@ -1,13 +1,24 @@
 use bevy::{
-    ecs::{entity::MapEntities, reflect::ReflectMapEntities},
+    ecs::{
+        entity::{EntityHashMap, MapEntities},
+        reflect::ReflectMapEntities,
+    },
     prelude::*,
-    tasks::IoTaskPool,
-    utils::HashSet,
+    reflect::{serde::ReflectSerializer, ReflectMut, TypeRegistry},
+    scene::DynamicEntity,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+    utils::{HashMap, HashSet},
 };
+#[cfg(feature = "editor")]
+use space_shared::toast::ToastMessage;
 use space_shared::{EditorPrefabPath, PrefabMarker, PrefabMemoryCache};
 use std::{any::TypeId, fs, io::Write};
 
-use crate::prelude::{EditorRegistry, EditorRegistryExt, SceneAutoChild};
+use crate::{
+    guid::prepare_prefab_guids,
+    prelude::{EditorRegistry, EditorRegistryExt, SceneAutoChild},
+    scene_format::SceneFormat,
+};
 
 #[derive(Reflect, Default, Component, Clone)]
 #[reflect(Component, MapEntities)]
@@ -37,8 +48,15 @@ struct SaveResourcesPrefabPlugin;
 impl Plugin for SaveResourcesPrefabPlugin {
     fn build(&self, app: &mut App) {
         app.editor_registry::<ChildrenPrefab>();
+        app.register_type::<SceneSerializationSettings>()
+            .init_resource::<SceneSerializationSettings>();
 
-        app.init_resource::<SaveConfig>().init_state::<SaveState>();
+        app.init_resource::<SaveConfig>()
+            .init_resource::<AssetFolder>()
+            .init_state::<SaveState>();
+        app.init_resource::<SceneSaveTasks>()
+            .init_resource::<SavedSceneEntities>()
+            .add_event::<SceneSaved>();
     }
 }
 
@@ -53,12 +71,14 @@ impl Plugin for SavePrefabPlugin {
             OnEnter(SaveState::Save),
             (
                 prepare_children,
+                prepare_prefab_guids,
                 apply_deferred,
                 serialize_scene,
                 delete_prepared_children,
             )
                 .chain(),
         );
+        app.add_systems(Update, finish_scene_saves);
     }
 }
 
@@ -69,15 +89,76 @@ pub struct SaveConfig {
     pub path: Option<EditorPrefabPath>,
 }
 
+/// Folder of asset files, must be the same as `AssetPlugin::file_path`.
+/// Used to find files of loaded prefabs when editor writes them
+#[derive(Resource, Clone, Debug)]
+pub struct AssetFolder(pub String);
+
+impl Default for AssetFolder {
+    fn default() -> Self {
+        Self("assets".to_string())
+    }
+}
+
+impl AssetFolder {
+    /// File of the asset by its asset path
+    pub fn file(&self, asset_path: &str) -> String {
+        format!("{}/{}", self.0, asset_path)
+    }
+}
+
+/// Options of scene file content
+#[derive(Resource, Reflect, Clone, Default, Debug)]
+#[reflect(Resource, Default)]
+pub struct SceneSerializationSettings {
+    /// Save entities and components in a stable order with entity ids renumbered from zero.
+    /// Saved file changes only when the scene is changed, which keeps version control diffs small
+    pub deterministic: bool,
+}
+
 /// State system using to enable slow logic of saving
 #[cfg(not(tarpaulin_include))]
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum SaveState {
     Save,
+    /// Scene is serialized, files are still being written
+    Writing,
     #[default]
     Idle,
 }
 
+/// Sent when scene save is finished or failed
+#[derive(Event, Clone, Debug)]
+pub struct SceneSaved {
+    pub path: EditorPrefabPath,
+    pub result: Result<(), String>,
+}
+
+/// Entities of the last scene serialized to file. Files saved next to the scene,
+/// like persistent undo history, refer to entities by their ids in the scene file
+#[derive(Resource, Default, Clone)]
+pub struct SavedSceneEntities {
+    pub path: String,
+    /// World entities mapped to their ids in the scene file
+    pub entity_map: EntityHashMap<Entity>,
+}
+
+/// Scene files which are being written in background
+#[derive(Resource, Default)]
+pub struct SceneSaveTasks {
+    tasks: Vec<(String, Task<Result<(), String>>)>,
+}
+
+impl SceneSaveTasks {
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn is_saving(&self, path: &str) -> bool {
+        self.tasks.iter().any(|(task_path, _)| task_path == path)
+    }
+}
+
 fn prepare_children(
     mut commands: Commands,
     query: Query<(Entity, &Children), (With<PrefabMarker>, Without<SceneAutoChild>)>,
@@ -112,66 +193,514 @@ pub fn serialize_scene(world: &mut World) {
         warn!("Saving empty scene");
     }
 
-    let registry = world.resource::<EditorRegistry>().clone();
-    let allow_types: Vec<TypeId> = registry
-        .registry
-        .read()
-        .iter()
-        .map(|a| a.type_id())
-        .collect();
-    let mut builder = DynamicSceneBuilder::from_world(world);
-    builder = builder
-        .allow_all()
-        .with_filter(SceneFilter::Allowlist(HashSet::from_iter(
-            allow_types.iter().cloned(),
-        )))
-        .extract_entities(entities.iter().copied());
-    let scene = builder.build();
+    let mut scene = extract_prefab_scene(world, entities.iter().copied());
+    scene.resources = extract_scene_resources(world);
+    let entity_map = apply_serialization_settings(world, &mut scene);
 
-    let res = scene.serialize_ron(world.resource::<AppTypeRegistry>());
+    let format = match &config.path {
+        Some(EditorPrefabPath::File(path)) => SceneFormat::from_path(path),
+        _ => SceneFormat::Ron,
+    };
+    let res = format.serialize(&scene, world.resource::<AppTypeRegistry>());
 
-    if let Ok(str) = res {
-        // Write the scene RON data to file
+    if let Ok(data) = res {
+        // Write the scene data to file
         let path = config.path;
         if let Some(path) = path {
             match path {
                 EditorPrefabPath::File(path) => {
-                    IoTaskPool::get()
-                        .spawn(async move {
-                            fs::OpenOptions::new()
-                                .create(true)
-                                .truncate(true)
-                                .append(false)
-                                .write(true)
-                                .open(&path)
-                                .and_then(|mut file| file.write(str.as_bytes()))
-                                .inspect_err(|e| error!("Error while writing scene to file: {e}"))
-                                .expect("Error while writing scene to file");
-                            info!("Saved prefab to file {}", path);
-                        })
-                        .detach();
+                    world.insert_resource(SavedSceneEntities {
+                        path: path.clone(),
+                        entity_map,
+                    });
+                    spawn_scene_write(world, path, data);
                 }
                 EditorPrefabPath::MemoryCache => {
                     let handle = world.resource_mut::<Assets<DynamicScene>>().add(scene);
                     world.resource_mut::<PrefabMemoryCache>().scene = Some(handle);
+                    world.send_event(SceneSaved {
+                        path: EditorPrefabPath::MemoryCache,
+                        result: Ok(()),
+                    });
                 }
             }
         }
     } else if let Err(e) = res {
         // Any ideas on how to test this error case?
         #[cfg_attr(tarpaulin, ignore)]
-        let err = format!("failed to serialize prefab: {:?}", e);
+        let err = format!("failed to serialize prefab: {}", e);
         #[cfg(feature = "editor")]
         world.send_event(space_shared::toast::ToastMessage::new(
             &err,
             space_shared::toast::ToastKind::Error,
         ));
         error!(err);
+        if let Some(path) = config.path {
+            world.send_event(SceneSaved {
+                path,
+                result: Err(err),
+            });
+        }
     }
 
+    //Saving is finished only when all files are written
+    let next_state = if world.resource::<SceneSaveTasks>().is_empty() {
+        SaveState::Idle
+    } else {
+        SaveState::Writing
+    };
+    world.resource_mut::<NextState<SaveState>>().set(next_state);
+}
+
+/// Write scene file in background. [`SceneSaved`] is sent when the file is written
+pub(crate) fn spawn_scene_write(world: &mut World, path: String, data: Vec<u8>) {
+    let task_path = path.clone();
+    let task = IoTaskPool::get()
+        .spawn(async move { write_scene_file(&task_path, &data).map_err(|err| err.to_string()) });
     world
-        .resource_mut::<NextState<SaveState>>()
-        .set(SaveState::Idle);
+        .resource_mut::<SceneSaveTasks>()
+        .tasks
+        .push((path, task));
+}
+
+/// Report finished file writes and finish saving when all of them are done
+fn finish_scene_saves(
+    mut tasks: ResMut<SceneSaveTasks>,
+    mut events: EventWriter<SceneSaved>,
+    state: Res<State<SaveState>>,
+    mut next_state: ResMut<NextState<SaveState>>,
+    #[cfg(feature = "editor")] mut toast: EventWriter<ToastMessage>,
+) {
+    tasks.tasks.retain_mut(|(path, task)| {
+        let Some(result) = block_on(future::poll_once(task)) else {
+            return true;
+        };
+        match &result {
+            Ok(()) => info!("Saved prefab to file {}", path),
+            Err(err) => {
+                let err = format!("Failed to save scene to file {}:\n{}", path, err);
+                #[cfg(feature = "editor")]
+                toast.send(ToastMessage::new(
+                    &err,
+                    space_shared::toast::ToastKind::Error,
+                ));
+                error!(err);
+            }
+        }
+        events.send(SceneSaved {
+            path: EditorPrefabPath::File(path.clone()),
+            result,
+        });
+        false
+    });
+
+    if tasks.tasks.is_empty() && *state.get() == SaveState::Writing {
+        next_state.set(SaveState::Idle);
+    }
+}
+
+/// Write serialized scene to file, replacing its old content
+pub fn write_scene_file(path: &str, data: &[u8]) -> std::io::Result<()> {
+    fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .append(false)
+        .write(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(data))
+}
+
+/// Origin of the prefab which is saved from selected entities
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefabPivot {
+    /// Center of selected entities
+    #[default]
+    Center,
+    /// Center of selected entities on the level of the lowest one
+    BottomCenter,
+    /// Entities keep their world positions
+    WorldOrigin,
+}
+
+/// Entities which were saved as a new prefab
+pub struct SavedSelection {
+    /// Selected entities without selected ancestors. They are top level entities of the prefab
+    pub roots: Vec<Entity>,
+    /// World position of the prefab origin
+    pub pivot: Vec3,
+    /// File of the prefab. [`SceneSaved`] is sent for it when the file is written
+    pub file: String,
+}
+
+/// Save entities with all their descendants to the new prefab file.
+/// Top level entities are placed relative to the pivot, so an instance spawned at the pivot
+/// looks exactly as the saved entities. Path is relative to the assets folder.
+/// File is written in background like saved scenes
+pub fn save_entities_as_prefab(
+    world: &mut World,
+    entities: &[Entity],
+    pivot: PrefabPivot,
+    path: &str,
+) -> Result<SavedSelection, String> {
+    prepare_prefab_guids(world);
+    let is_saved = |world: &World, entity: Entity| {
+        world.get::<PrefabMarker>(entity).is_some() && world.get::<SceneAutoChild>(entity).is_none()
+    };
+    let selected: HashSet<Entity> = entities
+        .iter()
+        .copied()
+        .filter(|entity| is_saved(world, *entity))
+        .collect();
+    let mut roots: Vec<Entity> = vec![];
+    for entity in entities.iter().copied() {
+        let mut ancestors = std::iter::successors(world.get::<Parent>(entity), |parent| {
+            world.get::<Parent>(parent.get())
+        });
+        if selected.contains(&entity)
+            && !roots.contains(&entity)
+            && !ancestors.any(|parent| selected.contains(&parent.get()))
+        {
+            roots.push(entity);
+        }
+    }
+    if roots.is_empty() {
+        return Err("No scene entities selected".to_string());
+    }
+
+    let mut saved = vec![];
+    let mut stack = roots.clone();
+    while let Some(entity) = stack.pop() {
+        saved.push(entity);
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.iter().filter(|child| is_saved(world, **child)));
+        }
+    }
+
+    let positions: Vec<Vec3> = roots
+        .iter()
+        .map(|root| {
+            world
+                .get::<GlobalTransform>(*root)
+                .map(|transform| transform.translation())
+                .unwrap_or_default()
+        })
+        .collect();
+    let min = positions.iter().fold(Vec3::MAX, |min, pos| min.min(*pos));
+    let max = positions.iter().fold(Vec3::MIN, |max, pos| max.max(*pos));
+    let pivot = match pivot {
+        PrefabPivot::Center => (min + max) / 2.0,
+        PrefabPivot::BottomCenter => Vec3::new((min.x + max.x) / 2.0, min.y, (min.z + max.z) / 2.0),
+        PrefabPivot::WorldOrigin => Vec3::ZERO,
+    };
+
+    //Hierarchy is saved only inside of the selection
+    let saved_set: HashSet<Entity> = saved.iter().copied().collect();
+    let mut prepared = vec![];
+    for entity in saved.iter() {
+        let Some(children) = world.get::<Children>(*entity) else {
+            continue;
+        };
+        let children: Vec<Entity> = children
+            .iter()
+            .copied()
+            .filter(|child| saved_set.contains(child))
+            .collect();
+        if !children.is_empty() {
+            world.entity_mut(*entity).insert(ChildrenPrefab(children));
+            prepared.push(*entity);
+        }
+    }
+    let mut scene = extract_prefab_scene(world, saved.iter().copied());
+    for entity in prepared {
+        world.entity_mut(entity).remove::<ChildrenPrefab>();
+    }
+
+    for dyn_entity in scene.entities.iter_mut() {
+        if !roots.contains(&dyn_entity.entity) {
+            continue;
+        }
+        let Some(global) = world.get::<GlobalTransform>(dyn_entity.entity) else {
+            continue;
+        };
+        let mut transform = global.compute_transform();
+        transform.translation -= pivot;
+        for component in dyn_entity.components.iter_mut() {
+            if component
+                .get_represented_type_info()
+                .is_some_and(|info| info.type_path() == Transform::type_path())
+            {
+                *component = Box::new(transform);
+            }
+        }
+    }
+    apply_serialization_settings(world, &mut scene);
+
+    let data =
+        SceneFormat::from_path(path).serialize(&scene, world.resource::<AppTypeRegistry>())?;
+    let file = world.resource::<AssetFolder>().file(path);
+    if let Some(dir) = std::path::Path::new(&file).parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    spawn_scene_write(world, file.clone(), data);
+
+    Ok(SavedSelection { roots, pivot, file })
+}
+
+/// Extract entities with all their components registered in [`EditorRegistry`]
+pub fn extract_prefab_scene(world: &World, entities: impl Iterator<Item = Entity>) -> DynamicScene {
+    let registry = world.resource::<EditorRegistry>().clone();
+    let allow_types: Vec<TypeId> = registry
+        .registry
+        .read()
+        .iter()
+        .map(|a| a.type_id())
+        .collect();
+    DynamicSceneBuilder::from_world(world)
+        .allow_all()
+        .with_filter(SceneFilter::Allowlist(HashSet::from_iter(
+            allow_types.iter().cloned(),
+        )))
+        .extract_entities(entities)
+        .build()
+}
+
+/// Extract resources registered with [`EditorRegistryExt::editor_scene_resource`]
+pub fn extract_scene_resources(world: &World) -> Vec<Box<dyn Reflect>> {
+    let allow_types = world.resource::<EditorRegistry>().scene_resources.clone();
+    DynamicSceneBuilder::from_world(world)
+        .with_resource_filter(SceneFilter::Allowlist(allow_types))
+        .extract_resources()
+        .build()
+        .resources
+}
+
+/// Apply [`SceneSerializationSettings`] to the extracted scene. Returns world entities
+/// mapped to their ids in the scene
+pub(crate) fn apply_serialization_settings(
+    world: &World,
+    scene: &mut DynamicScene,
+) -> EntityHashMap<Entity> {
+    if world
+        .get_resource::<SceneSerializationSettings>()
+        .is_some_and(|settings| settings.deterministic)
+    {
+        make_scene_deterministic(scene, &world.resource::<AppTypeRegistry>().read())
+    } else {
+        scene
+            .entities
+            .iter()
+            .map(|entity| (entity.entity, entity.entity))
+            .collect()
+    }
+}
+
+/// Reorder scene content so that the same scene is always serialized to the same text.
+/// Top level entities are sorted by name and content, children follow their parents in
+/// [`ChildrenPrefab`] order, components and resources are sorted by type path and
+/// entities are renumbered densely from zero. Returns old entity ids mapped to the new ones
+pub fn make_scene_deterministic(
+    scene: &mut DynamicScene,
+    registry: &TypeRegistry,
+) -> EntityHashMap<Entity> {
+    let ids: HashSet<Entity> = scene.entities.iter().map(|entity| entity.entity).collect();
+
+    let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for entity in scene.entities.iter() {
+        let Some(prefab_children) = entity
+            .components
+            .iter()
+            .find(|component| type_path(component.as_reflect()) == ChildrenPrefab::type_path())
+            .and_then(|component| ChildrenPrefab::from_reflect(component.as_reflect()))
+        else {
+            continue;
+        };
+        children.insert(
+            entity.entity,
+            prefab_children
+                .0
+                .into_iter()
+                .filter(|child| ids.contains(child))
+                .collect(),
+        );
+    }
+    let child_ids: HashSet<Entity> = children.values().flatten().copied().collect();
+
+    //Runtime ids must not affect the order
+    let placeholders: EntityHashMap<Entity> = ids
+        .iter()
+        .map(|entity| (*entity, Entity::PLACEHOLDER))
+        .collect();
+    let mut keys: Vec<((String, String), Entity)> = scene
+        .entities
+        .iter()
+        .map(|entity| {
+            (
+                entity_sort_key(entity, &placeholders, registry),
+                entity.entity,
+            )
+        })
+        .collect();
+    keys.sort();
+
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    let roots = keys
+        .iter()
+        .map(|(_, entity)| *entity)
+        .filter(|entity| !child_ids.contains(entity));
+    //Entities which are not reachable from top level entities are placed at the end
+    let rest = keys.iter().map(|(_, entity)| *entity);
+    for start in roots.chain(rest) {
+        let mut stack = vec![start];
+        while let Some(entity) = stack.pop() {
+            if !visited.insert(entity) {
+                continue;
+            }
+            order.push(entity);
+            if let Some(children) = children.get(&entity) {
+                stack.extend(children.iter().rev());
+            }
+        }
+    }
+
+    let entity_map: EntityHashMap<Entity> = order
+        .iter()
+        .enumerate()
+        .map(|(index, entity)| (*entity, Entity::from_raw(index as u32)))
+        .collect();
+    let mut entities: EntityHashMap<DynamicEntity> = scene
+        .entities
+        .drain(..)
+        .map(|entity| (entity.entity, entity))
+        .collect();
+    for old in order {
+        let Some(mut entity) = entities.remove(&old) else {
+            continue;
+        };
+        entity.entity = entity_map[&old];
+        sort_and_map_values(&mut entity.components, &entity_map);
+        scene.entities.push(entity);
+    }
+    sort_and_map_values(&mut scene.resources, &entity_map);
+    entity_map
+}
+
+fn type_path(value: &dyn Reflect) -> &str {
+    value
+        .get_represented_type_info()
+        .map(|info| info.type_path())
+        .unwrap_or_else(|| value.reflect_type_path())
+}
+
+fn sort_and_map_values(values: &mut [Box<dyn Reflect>], entity_map: &EntityHashMap<Entity>) {
+    values.sort_by(|a, b| type_path(a.as_reflect()).cmp(type_path(b.as_reflect())));
+    for value in values.iter_mut() {
+        map_reflect_entities(value.as_reflect_mut(), entity_map);
+    }
+}
+
+/// Name and serialized components of the entity
+fn entity_sort_key(
+    entity: &DynamicEntity,
+    placeholders: &EntityHashMap<Entity>,
+    registry: &TypeRegistry,
+) -> (String, String) {
+    let mut name = String::new();
+    let mut components: Vec<Box<dyn Reflect>> = entity
+        .components
+        .iter()
+        .map(|component| component.clone_value())
+        .collect();
+    sort_and_map_values(&mut components, placeholders);
+    let mut content = String::new();
+    for component in components.iter() {
+        if type_path(component.as_reflect()) == Name::type_path() {
+            if let Some(component_name) = Name::from_reflect(component.as_reflect()) {
+                name = component_name.to_string();
+            }
+        }
+        let serializer = ReflectSerializer::new(component.as_reflect(), registry);
+        if let Ok(text) = ron::to_string(&serializer) {
+            content.push_str(&text);
+        }
+    }
+    (name, content)
+}
+
+/// Replace entities inside of reflected value, entities which are not in the map are kept
+fn map_reflect_entities(value: &mut dyn Reflect, entity_map: &EntityHashMap<Entity>) {
+    visit_reflect_mut(value, &mut |value| {
+        let Some(entity) = value.downcast_mut::<Entity>() else {
+            return false;
+        };
+        if let Some(mapped) = entity_map.get(entity) {
+            *entity = *mapped;
+        }
+        true
+    });
+}
+
+/// Call visitor for the value and all its nested fields and items.
+/// Nested values are skipped when visitor returns true
+pub(crate) fn visit_reflect_mut(
+    value: &mut dyn Reflect,
+    visitor: &mut dyn FnMut(&mut dyn Reflect) -> bool,
+) {
+    if visitor(value) {
+        return;
+    }
+    match value.reflect_mut() {
+        ReflectMut::Struct(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_at_mut(index) {
+                    visit_reflect_mut(field, visitor);
+                }
+            }
+        }
+        ReflectMut::TupleStruct(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    visit_reflect_mut(field, visitor);
+                }
+            }
+        }
+        ReflectMut::Tuple(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_mut(index) {
+                    visit_reflect_mut(field, visitor);
+                }
+            }
+        }
+        ReflectMut::List(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    visit_reflect_mut(item, visitor);
+                }
+            }
+        }
+        ReflectMut::Array(value) => {
+            for index in 0..value.len() {
+                if let Some(item) = value.get_mut(index) {
+                    visit_reflect_mut(item, visitor);
+                }
+            }
+        }
+        ReflectMut::Map(value) => {
+            for index in 0..value.len() {
+                if let Some((_, item)) = value.get_at_mut(index) {
+                    visit_reflect_mut(item, visitor);
+                }
+            }
+        }
+        ReflectMut::Enum(value) => {
+            for index in 0..value.field_len() {
+                if let Some(field) = value.field_at_mut(index) {
+                    visit_reflect_mut(field, visitor);
+                }
+            }
+        }
+        ReflectMut::Value(_) => {}
+    }
 }
 
 #[cfg(test)]
@@ -223,6 +752,92 @@ mod tests {
         assert!(contents.contains("space_shared::PrefabMarker"));
     }
 
+    fn wait_scene_saved(app: &mut App) -> SceneSaved {
+        for _ in 0..100 {
+            app.update();
+            let mut events = app.world.resource_mut::<Events<SceneSaved>>();
+            if let Some(event) = events.drain().next() {
+                return event;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("Scene was not saved");
+    }
+
+    fn save_app(path: &str) -> App {
+        let save_config = SaveConfig {
+            path: Some(EditorPrefabPath::File(String::from(path))),
+        };
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            EditorRegistryPlugin {},
+            SaveResourcesPrefabPlugin {},
+        ))
+        .insert_resource(save_config)
+        .init_resource::<PrefabMemoryCache>()
+        .add_systems(Update, finish_scene_saves)
+        .editor_registry::<Name>()
+        .editor_registry::<PrefabMarker>();
+        #[cfg(feature = "editor")]
+        app.add_event::<space_shared::toast::ToastMessage>();
+        app.world.spawn(PrefabMarker).insert(Name::new("saved"));
+        app.update();
+        app
+    }
+
+    #[test]
+    fn save_is_finished_after_file_is_written() {
+        let file = "test_scene_saved.ron";
+        let mut app = save_app(file);
+
+        serialize_scene(&mut app.world);
+        let event = wait_scene_saved(&mut app);
+        app.update();
+
+        let contents = std::fs::read_to_string(file);
+        let _ = std::fs::remove_file(file);
+        assert!(matches!(event.path, EditorPrefabPath::File(path) if path == file));
+        assert_eq!(event.result, Ok(()));
+        assert!(contents.unwrap().contains("saved"));
+        assert!(app.world.resource::<SceneSaveTasks>().is_empty());
+        assert_eq!(
+            *app.world.resource::<State<SaveState>>().get(),
+            SaveState::Idle
+        );
+    }
+
+    #[test]
+    fn binary_format_is_selected_by_extension() {
+        let file = "test_scene_saved.scn.bin";
+        let mut app = save_app(file);
+
+        serialize_scene(&mut app.world);
+        let event = wait_scene_saved(&mut app);
+
+        let contents = std::fs::read(file);
+        let _ = std::fs::remove_file(file);
+        assert_eq!(event.result, Ok(()));
+        let registry = app.world.resource::<AppTypeRegistry>();
+        let scene = SceneFormat::Binary
+            .deserialize(&contents.unwrap(), &registry.read())
+            .unwrap();
+        let text = scene.serialize_ron(registry).unwrap();
+        assert!(text.contains("saved"));
+    }
+
+    #[test]
+    fn failed_save_is_reported() {
+        let mut app = save_app("not_existing_dir/test_scene_saved.ron");
+
+        serialize_scene(&mut app.world);
+        let event = wait_scene_saved(&mut app);
+
+        assert!(event.result.is_err());
+    }
+
     #[test]
     fn save_to_memory() {
         let save_config = SaveConfig {
@@ -258,6 +873,50 @@ mod tests {
             .is_some());
     }
 
+    #[test]
+    fn registered_resources_are_saved_with_scene() {
+        #[derive(Resource, Reflect, Default)]
+        #[reflect(Resource)]
+        struct Weather(f32);
+
+        #[derive(Resource, Reflect, Default)]
+        #[reflect(Resource)]
+        struct NotSaved;
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            EditorRegistryPlugin {},
+            SaveResourcesPrefabPlugin {},
+        ))
+        .insert_resource(SaveConfig {
+            path: Some(EditorPrefabPath::MemoryCache),
+        })
+        .init_resource::<PrefabMemoryCache>()
+        .editor_scene_resource::<Weather>()
+        .register_type::<NotSaved>()
+        .insert_resource(Weather(2.0))
+        .init_resource::<NotSaved>();
+        #[cfg(feature = "editor")]
+        app.add_event::<space_shared::toast::ToastMessage>();
+        app.update();
+
+        serialize_scene(&mut app.world);
+
+        let handle = app
+            .world
+            .resource::<PrefabMemoryCache>()
+            .scene
+            .clone()
+            .unwrap();
+        let scenes = app.world.resource::<Assets<DynamicScene>>();
+        let resources = &scenes.get(handle).unwrap().resources;
+        assert_eq!(resources.len(), 1);
+        assert_eq!(type_path(resources[0].as_reflect()), Weather::type_path());
+    }
+
     #[test]
     fn inserts_prepared_children_component() {
         let mut app = App::new();
@@ -295,6 +954,172 @@ mod tests {
         assert_eq!(query.iter(&app.world).count(), 0);
     }
 
+    #[test]
+    fn saves_selection_around_pivot() {
+        use bevy::scene::serde::SceneDeserializer;
+        use serde::de::DeserializeSeed;
+
+        let folder = std::env::temp_dir().join("space_prefab_save_selection");
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            EditorRegistryPlugin {},
+            SaveResourcesPrefabPlugin {},
+        ))
+        .insert_resource(AssetFolder(folder.display().to_string()))
+        .add_systems(Update, finish_scene_saves)
+        .register_type::<Vec<Entity>>()
+        .editor_registry::<Name>()
+        .editor_registry::<Transform>();
+        #[cfg(feature = "editor")]
+        app.add_event::<space_shared::toast::ToastMessage>();
+
+        let child = app
+            .world
+            .spawn((PrefabMarker, Name::new("child")))
+            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 1.0, 0.0,
+            )))
+            .id();
+        let parent = app
+            .world
+            .spawn((PrefabMarker, Name::new("parent")))
+            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                2.0, 0.0, 0.0,
+            )))
+            .add_child(child)
+            .id();
+        let other = app
+            .world
+            .spawn((PrefabMarker, Name::new("other")))
+            .insert(TransformBundle::from_transform(Transform::from_xyz(
+                4.0, 0.0, 0.0,
+            )))
+            .id();
+        app.world.spawn((PrefabMarker, Name::new("not selected")));
+        app.update();
+
+        let saved = save_entities_as_prefab(
+            &mut app.world,
+            &[child, parent, other],
+            PrefabPivot::Center,
+            "test_selection/saved.scn.ron",
+        )
+        .unwrap();
+        let event = wait_scene_saved(&mut app);
+        let contents = std::fs::read_to_string(&saved.file).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert!(matches!(event.path, EditorPrefabPath::File(path) if path == saved.file));
+        assert!(saved.file.ends_with("test_selection/saved.scn.ron"));
+        assert_eq!(saved.roots, vec![parent, other]);
+        assert_eq!(saved.pivot, Vec3::new(3.0, 0.0, 0.0));
+        assert!(!contents.contains("not selected"));
+        assert!(app.world.get::<ChildrenPrefab>(parent).is_none());
+
+        let registry = app.world.resource::<AppTypeRegistry>().read();
+        let mut deserializer = ron::de::Deserializer::from_str(&contents).unwrap();
+        let scene = SceneDeserializer {
+            type_registry: &registry,
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+        let mut translations: Vec<Vec3> = scene
+            .entities
+            .iter()
+            .flat_map(|entity| entity.components.iter())
+            .filter(|component| {
+                component
+                    .get_represented_type_info()
+                    .is_some_and(|info| info.type_path() == Transform::type_path())
+            })
+            .filter_map(|component| Transform::from_reflect(component.as_reflect()))
+            .map(|transform| transform.translation)
+            .collect();
+        translations.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(
+            translations,
+            vec![
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn deterministic_scene_does_not_depend_on_spawn_order() {
+        fn save(reversed: bool) -> String {
+            let mut app = App::new();
+            app.add_plugins((
+                MinimalPlugins,
+                HierarchyPlugin,
+                AssetPlugin::default(),
+                bevy::scene::ScenePlugin,
+                EditorRegistryPlugin {},
+                SaveResourcesPrefabPlugin {},
+            ))
+            .register_type::<Vec<Entity>>()
+            .editor_registry::<Name>()
+            .editor_registry::<PrefabMarker>()
+            .editor_registry::<Transform>();
+
+            //Shift runtime ids of the second world
+            if reversed {
+                for _ in 0..5 {
+                    let entity = app.world.spawn_empty().id();
+                    app.world.despawn(entity);
+                }
+            }
+            let mut names = vec!["b", "a", "c"];
+            if reversed {
+                names.reverse();
+            }
+            for name in names {
+                let child = app
+                    .world
+                    .spawn((PrefabMarker, Name::new(format!("{name} child"))))
+                    .id();
+                app.world
+                    .spawn((PrefabMarker, Name::new(name), Transform::default()))
+                    .add_child(child);
+            }
+            app.update();
+
+            let mut parents = app
+                .world
+                .query_filtered::<(Entity, &Children), With<PrefabMarker>>();
+            let prepared: Vec<(Entity, ChildrenPrefab)> = parents
+                .iter(&app.world)
+                .map(|(entity, children)| (entity, ChildrenPrefab::from_children(children)))
+                .collect();
+            for (entity, children) in prepared {
+                app.world.entity_mut(entity).insert(children);
+            }
+
+            let mut query = app.world.query_filtered::<Entity, With<PrefabMarker>>();
+            let mut scene = extract_prefab_scene(&app.world, query.iter(&app.world));
+            let registry = app.world.resource::<AppTypeRegistry>();
+            make_scene_deterministic(&mut scene, &registry.read());
+            scene.serialize_ron(registry).unwrap()
+        }
+
+        let first = save(false);
+        assert_eq!(first, save(true));
+        assert!(first.find("\"a\"").unwrap() < first.find("\"a child\"").unwrap());
+        assert!(first.find("\"a child\"").unwrap() < first.find("\"b\"").unwrap());
+        let id = |index: u32| Entity::from_raw(index).to_bits().to_string();
+        assert!(first.contains(&format!("{}: (", id(0))));
+        assert!(first.contains(&format!("{}: (", id(5))));
+        assert!(!first.contains(&id(6)));
+        //Child of "a" follows it
+        assert!(first.contains(&format!("([\n          {},\n        ])", id(1))));
+    }
+
     #[test]
     fn child_prefab_from_children() {
         let mut world = World::new();
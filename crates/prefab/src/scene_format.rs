@@ -0,0 +1,179 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::{TypeRegistry, TypeRegistryArc},
+    scene::serde::{SceneDeserializer, SceneSerializer},
+    utils::BoxedFuture,
+};
+use bincode::Options;
+use serde::de::DeserializeSeed;
+
+/// Extension of scene files in RON format
+pub const RON_SCENE_EXTENSION: &str = "scn.ron";
+
+/// Extension of scene files in binary format
+pub const BINARY_SCENE_EXTENSION: &str = "scn.bin";
+
+/// Encoding of scene files. It is selected by the file extension
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    /// Human readable RON text
+    #[default]
+    Ron,
+    /// Compact binary encoding, which is smaller and faster to load
+    Binary,
+}
+
+impl SceneFormat {
+    /// Format of the scene file with this path
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(&format!(".{}", BINARY_SCENE_EXTENSION)) {
+            Self::Binary
+        } else {
+            Self::Ron
+        }
+    }
+
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Ron => RON_SCENE_EXTENSION,
+            Self::Binary => BINARY_SCENE_EXTENSION,
+        }
+    }
+
+    pub fn serialize(
+        &self,
+        scene: &DynamicScene,
+        registry: &TypeRegistryArc,
+    ) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Ron => scene
+                .serialize_ron(registry)
+                .map(|text| text.into_bytes())
+                .map_err(|err| err.to_string()),
+            Self::Binary => bincode_options()
+                .serialize(&SceneSerializer::new(scene, registry))
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    pub fn deserialize(
+        &self,
+        bytes: &[u8],
+        registry: &TypeRegistry,
+    ) -> Result<DynamicScene, String> {
+        let scene_deserializer = SceneDeserializer {
+            type_registry: registry,
+        };
+        match self {
+            Self::Ron => {
+                let mut deserializer =
+                    ron::de::Deserializer::from_bytes(bytes).map_err(|err| err.to_string())?;
+                scene_deserializer
+                    .deserialize(&mut deserializer)
+                    .map_err(|err| err.to_string())
+            }
+            Self::Binary => bincode_options()
+                .deserialize_seed(scene_deserializer, bytes)
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// Is file a scene in any of supported formats
+pub fn is_scene_file(path: &str) -> bool {
+    path.ends_with(&format!(".{}", RON_SCENE_EXTENSION))
+        || path.ends_with(&format!(".{}", BINARY_SCENE_EXTENSION))
+}
+
+/// Path of the scene file without extension
+pub fn strip_scene_extension(path: &str) -> &str {
+    path.strip_suffix(&format!(".{}", RON_SCENE_EXTENSION))
+        .or_else(|| path.strip_suffix(&format!(".{}", BINARY_SCENE_EXTENSION)))
+        .unwrap_or(path)
+}
+
+/// Loads scenes saved in [`SceneFormat::Binary`]
+pub struct BinarySceneLoader {
+    type_registry: TypeRegistryArc,
+}
+
+impl FromWorld for BinarySceneLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            type_registry: world.resource::<AppTypeRegistry>().0.clone(),
+        }
+    }
+}
+
+impl AssetLoader for BinarySceneLoader {
+    type Asset = DynamicScene;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<DynamicScene, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            SceneFormat::Binary
+                .deserialize(&bytes, &self.type_registry.read())
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[BINARY_SCENE_EXTENSION]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_is_selected_by_extension() {
+        assert_eq!(
+            SceneFormat::from_path("levels/level.scn.bin"),
+            SceneFormat::Binary
+        );
+        assert_eq!(
+            SceneFormat::from_path("levels/level.scn.ron"),
+            SceneFormat::Ron
+        );
+        assert!(is_scene_file("level.scn.bin"));
+        assert!(!is_scene_file("level.variant.ron"));
+        assert_eq!(
+            strip_scene_extension("levels/level.scn.bin"),
+            "levels/level"
+        );
+    }
+
+    #[test]
+    fn binary_scene_roundtrip() {
+        let mut app = App::new();
+        app.register_type::<Name>();
+        app.world.spawn(Name::new("first"));
+        app.world.spawn(Name::new("second"));
+        let registry = app.world.resource::<AppTypeRegistry>();
+        let scene = DynamicScene::from_world(&app.world);
+
+        let binary = SceneFormat::Binary.serialize(&scene, registry).unwrap();
+        let ron = SceneFormat::Ron.serialize(&scene, registry).unwrap();
+        assert!(binary.len() < ron.len());
+
+        let loaded = SceneFormat::Binary
+            .deserialize(&binary, &registry.read())
+            .unwrap();
+        assert_eq!(loaded.entities.len(), 2);
+        assert_eq!(SceneFormat::Ron.serialize(&loaded, registry).unwrap(), ron);
+    }
+}
@@ -0,0 +1,480 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*, utils::HashSet};
+
+use crate::{
+    component::EntityLink,
+    dependencies::{asset_file, component_files, is_type, normalize_prefab_path},
+    guid::PrefabGuid,
+    plugins::BasePrefabPlugin,
+    save::visit_reflect_mut,
+    scene_format::{is_scene_file, SceneFormat},
+};
+
+/// Problem found in a scene file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneIssue {
+    /// File can not be read
+    Read(String),
+    /// Component or resource type is not registered in the type registry
+    UnregisteredType(String),
+    /// Scene content does not match registered types
+    Deserialize(String),
+    /// Scene can not be spawned to the world
+    Spawn(String),
+    /// File used by a component does not exist in the assets folder
+    MissingFile {
+        entity: Entity,
+        component: String,
+        path: String,
+    },
+    /// [`EntityLink`] points to an entity which is not in the scene
+    DanglingEntityLink {
+        entity: Entity,
+        component: String,
+        target: Entity,
+    },
+}
+
+impl fmt::Display for SceneIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "can not read file: {}", err),
+            Self::UnregisteredType(type_path) => write!(f, "unregistered type {}", type_path),
+            Self::Deserialize(err) => write!(f, "can not deserialize scene: {}", err),
+            Self::Spawn(err) => write!(f, "can not spawn scene: {}", err),
+            Self::MissingFile {
+                entity,
+                component,
+                path,
+            } => write!(
+                f,
+                "{} of entity {:?} uses missing file {}",
+                component, entity, path
+            ),
+            Self::DanglingEntityLink {
+                entity,
+                component,
+                target,
+            } => write!(
+                f,
+                "{} of entity {:?} links to entity {:?} which is not in the scene",
+                component, entity, target
+            ),
+        }
+    }
+}
+
+/// Result of scene file validation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneReport {
+    /// Path of the scene relative to the assets folder
+    pub path: String,
+    pub issues: Vec<SceneIssue>,
+}
+
+impl SceneReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks scene files without the editor, for example in CI.
+/// Scenes are loaded into a headless app with [`MinimalPlugins`] and [`BasePrefabPlugin`].
+/// Types of the game must be registered in [`SceneValidator::app_mut`] before validation
+pub struct SceneValidator {
+    app: App,
+    assets_folder: PathBuf,
+}
+
+impl SceneValidator {
+    pub fn new(assets_folder: impl Into<PathBuf>) -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            BasePrefabPlugin,
+        ));
+        Self {
+            app,
+            assets_folder: assets_folder.into(),
+        }
+    }
+
+    /// App which holds the type registry used for validation
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    /// Validate all scene files in the folder and its subfolders.
+    /// Folder is relative to the assets folder
+    pub fn validate_folder(&mut self, folder: &str) -> Vec<SceneReport> {
+        let mut reports = vec![];
+        let mut stack = vec![self.assets_folder.join(folder)];
+        while let Some(dir) = stack.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    reports.push(SceneReport {
+                        path: dir.to_string_lossy().to_string(),
+                        issues: vec![SceneIssue::Read(err.to_string())],
+                    });
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                let file = entry.path();
+                if file.is_dir() {
+                    stack.push(file);
+                    continue;
+                }
+                let Ok(relative) = file.strip_prefix(&self.assets_folder) else {
+                    continue;
+                };
+                let path = normalize_prefab_path(&relative.to_string_lossy());
+                if is_scene_file(&path) {
+                    reports.push(self.validate_file(&path));
+                }
+            }
+        }
+        reports.sort_by(|a, b| a.path.cmp(&b.path));
+        reports
+    }
+
+    /// Validate scene file. Path is relative to the assets folder
+    pub fn validate_file(&mut self, path: &str) -> SceneReport {
+        let mut report = SceneReport {
+            path: path.to_string(),
+            issues: vec![],
+        };
+        match std::fs::read(self.assets_folder.join(path)) {
+            Ok(bytes) => report.issues = self.validate_bytes(path, &bytes),
+            Err(err) => report.issues.push(SceneIssue::Read(err.to_string())),
+        }
+        report
+    }
+
+    /// Validate content of scene file. Format is selected by the path
+    pub fn validate_bytes(&mut self, path: &str, bytes: &[u8]) -> Vec<SceneIssue> {
+        let registry = self.app.world.resource::<AppTypeRegistry>().clone();
+        let format = SceneFormat::from_path(path);
+
+        //Binary scenes can not be read without registry, so only the first unregistered type
+        //is reported by the deserialization error
+        if format == SceneFormat::Ron {
+            let registry = registry.read();
+            let unregistered: Vec<SceneIssue> = ron_scene_type_paths(bytes)
+                .into_iter()
+                .filter(|type_path| registry.get_with_type_path(type_path).is_none())
+                .map(SceneIssue::UnregisteredType)
+                .collect();
+            if !unregistered.is_empty() {
+                return unregistered;
+            }
+        }
+
+        let mut scene = match format.deserialize(bytes, &registry.read()) {
+            Ok(scene) => scene,
+            Err(err) => return vec![SceneIssue::Deserialize(err)],
+        };
+
+        let mut issues = vec![];
+        if let Err(err) = self.spawn_scene(&scene) {
+            issues.push(SceneIssue::Spawn(err));
+        }
+        issues.extend(self.missing_files(&scene));
+        issues.extend(dangling_entity_links(&mut scene));
+        issues
+    }
+
+    /// Spawn scene to the app world and despawn it back
+    fn spawn_scene(&mut self, scene: &DynamicScene) -> Result<(), String> {
+        let mut entity_map = EntityHashMap::default();
+        let res = scene.write_to_world(&mut self.app.world, &mut entity_map);
+        for entity in entity_map.values() {
+            self.app.world.despawn(*entity);
+        }
+        res.map_err(|err| err.to_string())
+    }
+
+    fn missing_files(&self, scene: &DynamicScene) -> Vec<SceneIssue> {
+        let mut issues = vec![];
+        for entity in scene.entities.iter() {
+            for component in entity.components.iter() {
+                for (component, path) in component_files(component.as_reflect()) {
+                    if !self.file_exists(asset_file(&path)) {
+                        issues.push(SceneIssue::MissingFile {
+                            entity: entity.entity,
+                            component: component.to_string(),
+                            path,
+                        });
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    fn file_exists(&self, path: &str) -> bool {
+        self.assets_folder
+            .join(Path::new(&normalize_prefab_path(path)))
+            .is_file()
+    }
+}
+
+/// Type paths of all components and resources in RON scene. Empty if the file is not valid RON
+fn ron_scene_type_paths(bytes: &[u8]) -> Vec<String> {
+    let Ok(scene) = ron::de::from_bytes::<ron::Value>(bytes) else {
+        return vec![];
+    };
+
+    let mut type_paths = map_keys(ron_field(&scene, "resources"));
+    if let Some(ron::Value::Map(entities)) = ron_field(&scene, "entities") {
+        for entity in entities.values() {
+            type_paths.extend(map_keys(ron_field(entity, "components")));
+        }
+    }
+    type_paths.sort();
+    type_paths.dedup();
+    type_paths
+}
+
+/// Field of RON struct, which is parsed as a map without type information
+fn ron_field<'a>(value: &'a ron::Value, name: &str) -> Option<&'a ron::Value> {
+    let ron::Value::Map(map) = value else {
+        return None;
+    };
+    map.iter()
+        .find(|(key, _)| matches!(key, ron::Value::String(key) if key == name))
+        .map(|(_, value)| value)
+}
+
+fn map_keys(value: Option<&ron::Value>) -> Vec<String> {
+    let Some(ron::Value::Map(map)) = value else {
+        return vec![];
+    };
+    map.keys()
+        .filter_map(|key| match key {
+            ron::Value::String(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Links to entities which are not in the scene and have no [`PrefabGuid`] of a scene entity.
+/// Links to entities of other scenes can not be checked by one file, so they are reported too
+fn dangling_entity_links(scene: &mut DynamicScene) -> Vec<SceneIssue> {
+    let entities: HashSet<Entity> = scene.entities.iter().map(|entity| entity.entity).collect();
+    let guids: HashSet<PrefabGuid> = scene
+        .entities
+        .iter()
+        .flat_map(|entity| entity.components.iter())
+        .filter(|component| is_type::<PrefabGuid>(component.as_reflect()))
+        .filter_map(|component| PrefabGuid::from_reflect(component.as_reflect()))
+        .collect();
+
+    let mut issues = vec![];
+    for entity in scene.entities.iter_mut() {
+        let scene_entity = entity.entity;
+        for component in entity.components.iter_mut() {
+            let component_name = component
+                .get_represented_type_info()
+                .map(|info| info.type_path_table().short_path())
+                .unwrap_or_else(|| component.reflect_short_type_path())
+                .to_string();
+            visit_reflect_mut(component.as_reflect_mut(), &mut |value| {
+                if !is_type::<EntityLink>(value) {
+                    return false;
+                }
+                if let Some(link) = EntityLink::from_reflect(value) {
+                    let linked = link.entity == Entity::PLACEHOLDER
+                        || entities.contains(&link.entity)
+                        || link.guid.is_some_and(|guid| guids.contains(&guid));
+                    if !linked {
+                        issues.push(SceneIssue::DanglingEntityLink {
+                            entity: scene_entity,
+                            component: component_name.clone(),
+                            target: link.entity,
+                        });
+                    }
+                }
+                true
+            });
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::{AssetMesh, GltfPrefab},
+        editor_registry::EditorRegistryExt,
+        load::PrefabLoader,
+    };
+
+    #[derive(Component, Reflect, Clone, Default)]
+    #[reflect(Component, Default)]
+    struct Follow {
+        target: EntityLink,
+    }
+
+    const SCENE_FOLDER: &str = "test_validation";
+
+    fn validator() -> SceneValidator {
+        let mut validator = SceneValidator::new(SCENE_FOLDER);
+        validator.app_mut().editor_registry::<Follow>();
+        validator
+    }
+
+    fn scene_text(validator: &mut SceneValidator, spawn: impl FnOnce(&mut World)) -> String {
+        let mut world = World::new();
+        world.insert_resource(
+            validator
+                .app_mut()
+                .world
+                .resource::<AppTypeRegistry>()
+                .clone(),
+        );
+        spawn(&mut world);
+        let scene = DynamicScene::from_world(&world);
+        scene
+            .serialize_ron(world.resource::<AppTypeRegistry>())
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_scene_has_no_issues() {
+        let mut validator = validator();
+        let text = scene_text(&mut validator, |world| {
+            let target = world.spawn(Name::new("target")).id();
+            world.spawn(Follow {
+                target: EntityLink {
+                    entity: target,
+                    guid: None,
+                },
+            });
+            world.spawn(Follow::default());
+        });
+
+        assert_eq!(
+            validator.validate_bytes("level.scn.ron", text.as_bytes()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn reports_unregistered_types() {
+        let mut validator = validator();
+        let text = scene_text(&mut validator, |world| {
+            world.spawn(Name::new("entity"));
+        });
+        let text = text.replace("bevy_core::name::Name", "game::Unknown");
+
+        assert_eq!(
+            validator.validate_bytes("level.scn.ron", text.as_bytes()),
+            vec![SceneIssue::UnregisteredType("game::Unknown".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_deserialize_failure() {
+        let mut validator = validator();
+        let text = scene_text(&mut validator, |world| {
+            world.spawn(AssetMesh {
+                path: "mesh.glb".to_string(),
+            });
+        });
+        let text = text.replace("path:", "wrong_field:");
+
+        let issues = validator.validate_bytes("level.scn.ron", text.as_bytes());
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0], SceneIssue::Deserialize(_)));
+    }
+
+    #[test]
+    fn reports_missing_files_and_dangling_links() {
+        let mut validator = validator();
+        let mut follower = Entity::PLACEHOLDER;
+        let text = scene_text(&mut validator, |world| {
+            world.spawn(PrefabLoader {
+                path: "missing_prefab.scn.ron".to_string(),
+            });
+            world.spawn(GltfPrefab {
+                path: "models/missing.glb".to_string(),
+                scene: "Scene0".to_string(),
+            });
+            follower = world
+                .spawn(Follow {
+                    target: EntityLink {
+                        entity: Entity::from_raw(1000),
+                        guid: None,
+                    },
+                })
+                .id();
+        });
+
+        let issues = validator.validate_bytes("level.scn.ron", text.as_bytes());
+        assert_eq!(issues.len(), 3);
+        assert!(issues.contains(&SceneIssue::DanglingEntityLink {
+            entity: follower,
+            component: "Follow".to_string(),
+            target: Entity::from_raw(1000),
+        }));
+        let mut missing: Vec<&str> = issues
+            .iter()
+            .filter_map(|issue| match issue {
+                SceneIssue::MissingFile { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect();
+        missing.sort();
+        assert_eq!(
+            missing,
+            vec!["missing_prefab.scn.ron", "models/missing.glb"]
+        );
+    }
+
+    #[test]
+    fn validates_scene_folder() {
+        let mut validator = validator();
+        let house = scene_text(&mut validator, |world| {
+            world.spawn(Name::new("house"));
+        });
+        let level = scene_text(&mut validator, |world| {
+            world.spawn(PrefabLoader {
+                path: "./scenes/house.scn.ron".to_string(),
+            });
+        });
+
+        let folder = Path::new(SCENE_FOLDER).join("scenes");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("house.scn.ron"), house).unwrap();
+        std::fs::write(folder.join("level.scn.ron"), level).unwrap();
+        std::fs::write(folder.join("broken.scn.ron"), "(entities: 5)").unwrap();
+        std::fs::write(folder.join("notes.txt"), "not a scene").unwrap();
+        let reports = validator.validate_folder("scenes");
+        let _ = std::fs::remove_dir_all(SCENE_FOLDER);
+
+        let paths: Vec<&str> = reports.iter().map(|report| report.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "scenes/broken.scn.ron",
+                "scenes/house.scn.ron",
+                "scenes/level.scn.ron"
+            ]
+        );
+        assert!(matches!(
+            reports[0].issues.as_slice(),
+            [SceneIssue::Deserialize(_)]
+        ));
+        assert!(reports[1].is_valid());
+        assert!(reports[2].is_valid());
+    }
+}
@@ -1,11 +1,25 @@
 // Remove after update to newer rust version
 #![allow(clippy::type_complexity)]
+pub mod persistent_history;
 #[cfg(test)]
 mod tests;
 
-use std::sync::Arc;
-
-use bevy::{prelude::*, utils::HashMap};
+use std::{
+    any::{Any, TypeId},
+    collections::BTreeMap,
+    sync::Arc,
+    time::Duration,
+};
+
+use bevy::{
+    ecs::{entity::EntityHashMap, system::EntityCommands},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use persistent_history::{
+    store_entity, store_scene, store_value, StoredChange, StoredChangeRegistry, StoredChanges,
+    StoredEntityIds,
+};
 
 const MAX_REFLECT_RECURSION: i32 = 10;
 const AUTO_UNDO_LATENCY: i32 = 2;
@@ -21,10 +35,15 @@ impl Plugin for UndoPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ChangeChain>();
         app.init_resource::<UndoIgnoreStorage>();
+        app.init_resource::<AutoUndoResources>();
+        app.init_resource::<UndoIgnoreResources>();
         app.init_resource::<ChangeChainSettings>();
+        app.init_resource::<StoredChangeRegistry>();
+        persistent_history::register_types(app);
 
         app.add_event::<NewChange>();
         app.add_event::<UndoRedo>();
+        app.add_event::<UndoRedoFailed>();
 
         app.configure_sets(
             PostUpdate,
@@ -107,50 +126,64 @@ impl Default for OneFrameUndoIgnore {
 fn update_change_chain(
     mut buffer: Local<Vec<NewChange>>, //Buffer will use for chain reaction changes and collecting them together
     settings: Res<ChangeChainSettings>,
+    time: Res<Time<Real>>,
     mut change_chain: ResMut<ChangeChain>,
     mut events: EventReader<NewChange>,
 ) {
-    //collect buffer
-    let mut events_on_current_frame = 0;
-    for event in events.read() {
-        buffer.push(event.clone());
-        events_on_current_frame += 1;
-    }
-
-    if events_on_current_frame > 0 {
-        return;
-    }
-
-    if buffer.is_empty() {
-        return;
-    }
-
-    //Drop buffer to vec of arc
-    let mut new_changes = vec![];
-    for b in buffer.iter() {
-        new_changes.push(b.change.clone());
-        change_chain.changes_for_redo.clear();
-    }
+    let now = time.elapsed();
+    if change_chain.transaction.is_some() {
+        //Changes collected before transaction start are a separate step
+        if !buffer.is_empty() {
+            let new_changes = buffer.drain(..).map(|b| b.change).collect();
+            change_chain.push_changes(new_changes, None, now);
+        }
 
-    //Clear buffer
-    buffer.clear();
+        let Some(transaction) = change_chain.transaction.as_mut() else {
+            return;
+        };
+        transaction
+            .changes
+            .extend(events.read().map(|event| event.change.clone()));
+
+        match transaction.state {
+            TransactionState::Open => {}
+            TransactionState::Commit => {
+                if let Some(transaction) = change_chain.transaction.take() {
+                    info!("Committed undo transaction: {}", transaction.name);
+                    change_chain.push_changes(transaction.changes, Some(transaction.name), now);
+                }
+            }
+            TransactionState::Cancel => {
+                if let Some(transaction) = change_chain.transaction.take() {
+                    info!("Cancelled undo transaction: {}", transaction.name);
+                    change_chain.cancelled_changes = transaction.changes;
+                }
+            }
+        }
+    } else {
+        //collect buffer
+        let mut events_on_current_frame = 0;
+        for event in events.read() {
+            buffer.push(event.clone());
+            events_on_current_frame += 1;
+        }
 
-    match new_changes.len().cmp(&1) {
-        std::cmp::Ordering::Less => {}
-        std::cmp::Ordering::Equal => {
-            change_chain.changes.push(new_changes[0].clone());
+        if events_on_current_frame > 0 {
+            return;
         }
-        std::cmp::Ordering::Greater => {
-            change_chain.changes.push(Arc::new(ManyChanges {
-                changes: new_changes,
-            }));
+
+        if buffer.is_empty() {
+            return;
         }
-    };
 
-    if change_chain.changes.len() > settings.max_change_chain_size {
-        let count = change_chain.changes.len() - settings.max_change_chain_size;
-        change_chain.changes.drain(0..count);
+        //Drop buffer to vec of arc
+        let new_changes: Vec<_> = buffer.drain(..).map(|b| b.change).collect();
+        if !change_chain.coalesce(&new_changes, now) {
+            change_chain.push_changes(new_changes, None, now);
+        }
     }
+
+    change_chain.trim(settings.max_change_chain_size, settings.max_memory_usage);
 }
 
 fn clear_one_frame_ignore(
@@ -168,29 +201,38 @@ fn clear_one_frame_ignore(
 fn undo_redo_logic(world: &mut World) {
     world.resource_scope::<Events<UndoRedo>, _>(|world, mut events| {
         world.resource_scope::<ChangeChain, _>(|world, mut change_chain| {
+            //Cancelled transaction is reverted in reverse order and never gets to redo
+            let cancelled = std::mem::take(&mut change_chain.cancelled_changes);
+            for change in cancelled.iter().rev() {
+                let res = change.revert(world, &change_chain.entity_remap);
+                if let Err(err) = apply_change_result(world, &mut change_chain, res) {
+                    report_failure(world, change.as_ref(), err);
+                }
+            }
+
             {
                 let mut reader = events.get_reader();
                 for event in reader.read(&events) {
                     match event {
                         UndoRedo::Undo => {
-                            if let Some(change) = change_chain.changes.pop() {
-                                let res = change.revert(world, &change_chain.entity_remap).unwrap();
-                                if let ChangeResult::SuccessWithRemap(remap) = res {
-                                    change_chain.entity_remap.extend(remap);
-                                }
-                                change_chain.changes_for_redo.push(change);
-                            }
+                            undo_step(world, &mut change_chain);
                         }
                         UndoRedo::Redo => {
-                            if let Some(change) = change_chain.changes_for_redo.pop() {
-                                let inverse_change = change.get_inverse();
-                                let res = inverse_change
-                                    .revert(world, &change_chain.entity_remap)
-                                    .unwrap();
-                                if let ChangeResult::SuccessWithRemap(remap) = res {
-                                    change_chain.entity_remap.extend(remap);
-                                }
-                                change_chain.changes.push(change);
+                            if let Some(id) = change_chain.redo_target(change_chain.current) {
+                                redo_step(world, &mut change_chain, id);
+                            }
+                        }
+                        UndoRedo::JumpTo(target) => {
+                            let Some((undo_count, redo_path)) = change_chain.path_to(*target)
+                            else {
+                                warn!("Change {:?} is not in undo history", target);
+                                continue;
+                            };
+                            for _ in 0..undo_count {
+                                undo_step(world, &mut change_chain);
+                            }
+                            for id in redo_path {
+                                redo_step(world, &mut change_chain, id);
                             }
                         }
                     }
@@ -201,23 +243,438 @@ fn undo_redo_logic(world: &mut World) {
     });
 }
 
+/// Revert current change and move to its parent in the history tree
+fn undo_step(world: &mut World, change_chain: &mut ChangeChain) {
+    let Some(id) = change_chain.current else {
+        return;
+    };
+    let node = &change_chain.history[&id];
+    if node.quarantined.is_none() {
+        let change = node.change.clone();
+        let res = change.revert(world, &change_chain.entity_remap);
+        if let Err(err) = apply_change_result(world, change_chain, res) {
+            report_failure(world, change.as_ref(), err.clone());
+            quarantine(change_chain, id, err);
+        }
+    }
+    change_chain.current = change_chain.history[&id].parent;
+    change_chain.update_stacks();
+}
+
+/// Apply child change of current one and move to it in the history tree
+fn redo_step(world: &mut World, change_chain: &mut ChangeChain, id: ChangeId) {
+    let node = &change_chain.history[&id];
+    if node.quarantined.is_none() {
+        let inverse_change = node.change.get_inverse();
+        let res = inverse_change.revert(world, &change_chain.entity_remap);
+        if let Err(err) = apply_change_result(world, change_chain, res) {
+            report_failure(world, inverse_change.as_ref(), err.clone());
+            quarantine(change_chain, id, err);
+        }
+    }
+    change_chain.set_redo_target(change_chain.current, id);
+    change_chain.current = Some(id);
+    change_chain.update_stacks();
+}
+
+/// Store remap from change result. Errors of partially applied changes are reported right away
+fn apply_change_result(
+    world: &mut World,
+    change_chain: &mut ChangeChain,
+    res: Result<ChangeResult, String>,
+) -> Result<(), String> {
+    match res? {
+        ChangeResult::Success => {}
+        ChangeResult::SuccessWithRemap(remap) => {
+            extend_remap(&mut change_chain.entity_remap, remap);
+        }
+        ChangeResult::PartialSuccess { remap, errors } => {
+            extend_remap(&mut change_chain.entity_remap, remap);
+            for err in errors {
+                error!("Failed to apply part of grouped change: {}", err);
+                world.send_event(UndoRedoFailed { error: err });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn report_failure(world: &mut World, change: &(dyn EditorChange + Send + Sync), error: String) {
+    let error = format!("Failed to apply \"{}\": {}", change.debug_text(), error);
+    error!("{}", error);
+    world.send_event(UndoRedoFailed { error });
+}
+
+/// Failed change will be skipped by next undo and redo steps
+fn quarantine(change_chain: &mut ChangeChain, id: ChangeId, error: String) {
+    if let Some(node) = change_chain.history.get_mut(&id) {
+        node.quarantined = Some(error);
+    }
+}
+
+/// Id of the change in the undo history tree
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ChangeId(u64);
+
+/// Node of the undo history tree
+pub struct HistoryNode {
+    pub change: Arc<dyn EditorChange + Send + Sync>,
+    /// Change which was current when this change was made. None for the history root
+    pub parent: Option<ChangeId>,
+    /// Alternative changes made after this one, from the oldest to the newest
+    pub children: Vec<ChangeId>,
+    /// Child which will be applied on redo
+    redo_child: Option<ChangeId>,
+    /// Error of the last failed undo or redo. Quarantined change is skipped by undo and redo
+    pub quarantined: Option<String>,
+    /// Approximate memory used by the change in bytes
+    pub size: usize,
+    /// Time when the change was made or when the last change was merged into it
+    time: Duration,
+}
+
+/// Undo history. All changes are stored as a tree, so making a new change after undo
+/// does not destroy previous redo branch
 #[derive(Resource, Default)]
 pub struct ChangeChain {
+    /// Changes from the history root to the current state. Last one will be reverted on undo
     pub changes: Vec<Arc<dyn EditorChange + Send + Sync>>,
+    /// Changes of the active redo branch. Last one will be applied on redo
     pub changes_for_redo: Vec<Arc<dyn EditorChange + Send + Sync>>,
     entity_remap: HashMap<Entity, Entity>,
+    history: BTreeMap<ChangeId, HistoryNode>,
+    roots: Vec<ChangeId>,
+    root_redo_child: Option<ChangeId>,
+    current: Option<ChangeId>,
+    next_id: u64,
+    transaction: Option<UndoTransaction>,
+    cancelled_changes: Vec<Arc<dyn EditorChange + Send + Sync>>,
+}
+
+/// Named group of changes which will be stored as one undo step
+pub struct UndoTransaction {
+    pub name: String,
+    pub changes: Vec<Arc<dyn EditorChange + Send + Sync>>,
+    state: TransactionState,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TransactionState {
+    Open,
+    Commit,
+    Cancel,
+}
+
+impl ChangeChain {
+    /// Start collecting all new changes into one undo step with given name.
+    /// Changes will be collected until [`ChangeChain::commit_transaction`] or [`ChangeChain::cancel_transaction`]
+    pub fn begin_transaction(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if let Some(transaction) = &self.transaction {
+            warn!(
+                "Undo transaction {} started while {} is not finished",
+                name, transaction.name
+            );
+            return;
+        }
+        self.transaction = Some(UndoTransaction {
+            name,
+            changes: vec![],
+            state: TransactionState::Open,
+        });
+    }
+
+    /// Store all changes collected by current transaction as one labelled undo step
+    pub fn commit_transaction(&mut self) {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.state = TransactionState::Commit;
+        } else {
+            warn!("No undo transaction to commit");
+        }
+    }
+
+    /// Revert all changes collected by current transaction and forget them
+    pub fn cancel_transaction(&mut self) {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.state = TransactionState::Cancel;
+        } else {
+            warn!("No undo transaction to cancel");
+        }
+    }
+
+    /// Current transaction if it was started
+    pub const fn transaction(&self) -> Option<&UndoTransaction> {
+        self.transaction.as_ref()
+    }
+
+    /// Is current transaction waiting to be committed or cancelled.
+    /// Auto undo systems must send all delayed changes on this frame
+    pub fn is_transaction_closing(&self) -> bool {
+        self.transaction
+            .as_ref()
+            .is_some_and(|transaction| transaction.state != TransactionState::Open)
+    }
+
+    /// Merge single new change into the current one, see [`EditorChange::coalesce`].
+    /// Only the newest change of its branch can receive merged changes
+    fn coalesce(
+        &mut self,
+        new_changes: &[Arc<dyn EditorChange + Send + Sync>],
+        time: Duration,
+    ) -> bool {
+        let [change] = new_changes else {
+            return false;
+        };
+        let Some(node) = self.current.and_then(|id| self.history.get_mut(&id)) else {
+            return false;
+        };
+        if !node.children.is_empty() || node.quarantined.is_some() {
+            return false;
+        }
+        let Some(merged) = node
+            .change
+            .coalesce(change.as_ref(), time.saturating_sub(node.time))
+        else {
+            return false;
+        };
+        node.size = merged.approximate_size();
+        node.change = merged;
+        node.time = time;
+        self.update_stacks();
+        true
+    }
+
+    fn push_changes(
+        &mut self,
+        mut new_changes: Vec<Arc<dyn EditorChange + Send + Sync>>,
+        label: Option<String>,
+        time: Duration,
+    ) {
+        if new_changes.is_empty() {
+            return;
+        }
+
+        let change: Arc<dyn EditorChange + Send + Sync> =
+            if new_changes.len() == 1 && label.is_none() {
+                new_changes.remove(0)
+            } else {
+                Arc::new(ManyChanges {
+                    changes: new_changes,
+                    label,
+                })
+            };
+
+        let id = ChangeId(self.next_id);
+        self.next_id += 1;
+        let size = change.approximate_size();
+        self.history.insert(
+            id,
+            HistoryNode {
+                change,
+                parent: self.current,
+                children: vec![],
+                redo_child: None,
+                quarantined: None,
+                size,
+                time,
+            },
+        );
+        match self
+            .current
+            .and_then(|parent| self.history.get_mut(&parent))
+        {
+            Some(parent) => parent.children.push(id),
+            None => self.roots.push(id),
+        }
+        self.set_redo_target(self.current, id);
+        self.current = Some(id);
+        self.update_stacks();
+    }
+
+    /// Current position in the undo history. None if all changes were reverted
+    pub const fn current(&self) -> Option<ChangeId> {
+        self.current
+    }
+
+    /// All stored changes in the undo history tree
+    pub const fn history(&self) -> &BTreeMap<ChangeId, HistoryNode> {
+        &self.history
+    }
+
+    /// Changes made from the history root state, from the oldest to the newest
+    pub fn history_roots(&self) -> &[ChangeId] {
+        &self.roots
+    }
+
+    /// Approximate memory used by all stored changes in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.history.values().map(|node| node.size).sum()
+    }
+
+    /// Is change a part of the current state, i.e. it will be reverted by undo steps
+    pub fn is_applied(&self, id: ChangeId) -> bool {
+        self.ancestors(self.current).contains(&id)
+    }
+
+    /// Child change of the given node which will be applied on redo
+    pub fn redo_target(&self, node: Option<ChangeId>) -> Option<ChangeId> {
+        node.map_or(self.root_redo_child, |id| {
+            self.history.get(&id).and_then(|node| node.redo_child)
+        })
+    }
+
+    fn set_redo_target(&mut self, node: Option<ChangeId>, child: ChangeId) {
+        match node.and_then(|id| self.history.get_mut(&id)) {
+            Some(node) => node.redo_child = Some(child),
+            None => self.root_redo_child = Some(child),
+        }
+    }
+
+    fn ancestors(&self, node: Option<ChangeId>) -> Vec<ChangeId> {
+        let mut path = vec![];
+        let mut node = node;
+        while let Some(id) = node {
+            path.push(id);
+            node = self.history.get(&id).and_then(|node| node.parent);
+        }
+        path
+    }
+
+    /// Count of undo steps and list of redo steps needed to move from current state to target
+    pub fn path_to(&self, target: Option<ChangeId>) -> Option<(usize, Vec<ChangeId>)> {
+        if target.is_some_and(|id| !self.history.contains_key(&id)) {
+            return None;
+        }
+        let current_path = self.ancestors(self.current);
+        let mut target_path = self.ancestors(target);
+
+        //Remove common part of the paths
+        let common = current_path
+            .iter()
+            .rev()
+            .zip(target_path.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        target_path.truncate(target_path.len() - common);
+        target_path.reverse();
+
+        Some((current_path.len() - common, target_path))
+    }
+
+    /// Rebuild undo and redo stacks for the current branch
+    fn update_stacks(&mut self) {
+        self.changes = self
+            .ancestors(self.current)
+            .iter()
+            .rev()
+            .map(|id| self.history[id].change.clone())
+            .collect();
+
+        self.changes_for_redo.clear();
+        let mut node = self.redo_target(self.current);
+        while let Some(id) = node {
+            self.changes_for_redo.push(self.history[&id].change.clone());
+            node = self.redo_target(Some(id));
+        }
+        self.changes_for_redo.reverse();
+    }
+
+    /// Remove the oldest changes until history contains no more than max_size changes
+    /// and uses no more than max_memory_usage bytes. The newest change is never removed by memory limit
+    fn trim(&mut self, max_size: usize, max_memory_usage: usize) {
+        while self.history.len() > max_size
+            || (self.history.len() > 1 && self.memory_usage() > max_memory_usage)
+        {
+            //The oldest change is always a root of the history tree
+            let Some(oldest) = self.history.keys().next().copied() else {
+                break;
+            };
+            if self.ancestors(self.current).contains(&oldest) {
+                //State after the oldest change becomes new root state, so other root branches are unreachable
+                for root in self.roots.clone() {
+                    if root != oldest {
+                        self.remove_subtree(root);
+                    }
+                }
+                let node = self.history.remove(&oldest).unwrap();
+                for child in node.children.iter() {
+                    if let Some(child) = self.history.get_mut(child) {
+                        child.parent = None;
+                    }
+                }
+                if self.current == Some(oldest) {
+                    self.current = None;
+                }
+                self.roots = node.children;
+                self.root_redo_child = node.redo_child;
+            } else {
+                self.remove_subtree(oldest);
+                self.roots.retain(|id| *id != oldest);
+                if self.root_redo_child == Some(oldest) {
+                    self.root_redo_child = self.roots.last().copied();
+                }
+            }
+        }
+        self.update_stacks();
+    }
+
+    fn remove_subtree(&mut self, root: ChangeId) {
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.history.remove(&id) {
+                stack.extend(node.children);
+            }
+        }
+    }
+}
+
+/// Extension for [`Commands`] to control undo transactions
+pub trait UndoTransactionExt {
+    /// See [`ChangeChain::begin_transaction`]
+    fn begin_undo_transaction(&mut self, name: impl Into<String>);
+    /// See [`ChangeChain::commit_transaction`]
+    fn commit_undo_transaction(&mut self);
+    /// See [`ChangeChain::cancel_transaction`]
+    fn cancel_undo_transaction(&mut self);
+}
+
+impl UndoTransactionExt for Commands<'_, '_> {
+    fn begin_undo_transaction(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.add(move |world: &mut World| {
+            world.resource_mut::<ChangeChain>().begin_transaction(name);
+        });
+    }
+
+    fn commit_undo_transaction(&mut self) {
+        self.add(|world: &mut World| {
+            world.resource_mut::<ChangeChain>().commit_transaction();
+        });
+    }
+
+    fn cancel_undo_transaction(&mut self) {
+        self.add(|world: &mut World| {
+            world.resource_mut::<ChangeChain>().cancel_transaction();
+        });
+    }
 }
 
 #[derive(Resource, Reflect)]
 #[reflect(Resource, Default)]
 pub struct ChangeChainSettings {
     pub max_change_chain_size: usize,
+    /// Memory budget of undo history in bytes. The oldest changes are removed when it is exceeded
+    pub max_memory_usage: usize,
+    /// Save undo history next to the scene file and restore it when the scene is opened again
+    pub persistent_history: bool,
 }
 
 impl Default for ChangeChainSettings {
     fn default() -> Self {
         Self {
             max_change_chain_size: 200,
+            max_memory_usage: 256 * 1024 * 1024,
+            persistent_history: false,
         }
     }
 }
@@ -226,6 +683,46 @@ pub fn get_entity_with_remap(entity: Entity, entity_remap: &HashMap<Entity, Enti
     *entity_remap.get(&entity).unwrap_or(&entity)
 }
 
+/// Same as [`World::get_entity_mut`], but with error message for change revert
+pub fn get_entity_mut_or_err(
+    world: &mut World,
+    entity: Entity,
+) -> Result<EntityWorldMut<'_>, String> {
+    world
+        .get_entity_mut(entity)
+        .ok_or_else(|| format!("Entity {:?} does not exist", entity))
+}
+
+fn from_reflect_or_err<T: FromReflect>(value: &dyn Reflect) -> Result<T, String> {
+    T::from_reflect(value).ok_or_else(|| {
+        format!(
+            "Failed to create {} from reflect",
+            pretty_type_name::pretty_type_name::<T>()
+        )
+    })
+}
+
+/// Add new remaps and follow remap chains,
+/// so entity which was restored several times can still be found by its first id
+fn extend_remap(
+    entity_remap: &mut HashMap<Entity, Entity>,
+    remap: impl IntoIterator<Item = (Entity, Entity)>,
+) {
+    entity_remap.extend(remap);
+
+    let keys = entity_remap.keys().copied().collect::<Vec<_>>();
+    for key in keys {
+        let mut target = entity_remap[&key];
+        for _ in 0..entity_remap.len() {
+            match entity_remap.get(&target) {
+                Some(next) if *next != target => target = *next,
+                _ => break,
+            }
+        }
+        entity_remap.insert(key, target);
+    }
+}
+
 pub trait EditorChange {
     fn revert(
         &self,
@@ -236,17 +733,61 @@ pub trait EditorChange {
     fn debug_text(&self) -> String;
 
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync>;
+
+    /// Approximate memory used by the change in bytes. Used to keep undo history in memory budget
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Merge the next change into this one, so the result reverts both of them at once.
+    /// `elapsed` is time since this change was made. None if changes must stay separate undo steps
+    fn coalesce(
+        &self,
+        _next: &(dyn EditorChange + Send + Sync),
+        _elapsed: Duration,
+    ) -> Option<Arc<dyn EditorChange + Send + Sync>> {
+        None
+    }
+
+    /// Used to downcast the next change in [`EditorChange::coalesce`]
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Serializable form of the change for persistent undo history.
+    /// Changes without it can not be saved, so history before them is lost on save
+    fn to_stored(
+        &self,
+        _registry: &AppTypeRegistry,
+        _ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        None
+    }
 }
 
 pub enum ChangeResult {
     Success,
     SuccessWithRemap(Vec<(Entity, Entity)>),
+    /// Some of grouped changes failed, all others were applied
+    PartialSuccess {
+        remap: Vec<(Entity, Entity)>,
+        errors: Vec<String>,
+    },
+}
+
+/// Sent when undo or redo of a change failed
+#[derive(Event, Clone, Debug)]
+pub struct UndoRedoFailed {
+    pub error: String,
 }
 
 #[derive(Event)]
 pub enum UndoRedo {
     Undo,
     Redo,
+    /// Revert and redo all changes between current state and the given history node.
+    /// None means the state before all stored changes
+    JumpTo(Option<ChangeId>),
 }
 
 #[derive(Event, Clone)]
@@ -265,7 +806,7 @@ impl EditorChange for AddedEntity {
         entity_remap: &HashMap<Entity, Entity>,
     ) -> Result<ChangeResult, String> {
         let e = get_entity_with_remap(self.entity, entity_remap);
-        world.entity_mut(e).despawn_recursive();
+        get_entity_mut_or_err(world, e)?.despawn_recursive();
         world
             .resource_mut::<UndoIgnoreStorage>()
             .storage
@@ -279,14 +820,76 @@ impl EditorChange for AddedEntity {
     }
 
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
-        Arc::new(RemovedEntity {
-            entity: self.entity,
-        })
+        Arc::new(RemovedEntity::new(self.entity))
+    }
+
+    fn to_stored(
+        &self,
+        _registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        Some(
+            StoredChange::AddedEntity {
+                entity: store_entity(self.entity, ids),
+            }
+            .into(),
+        )
     }
 }
 
 pub struct RemovedEntity {
     pub entity: Entity,
+    /// Snapshot of the removed entity and its descendants.
+    /// Without snapshot only an empty entity with [`UndoMarker`] will be restored
+    pub snapshot: Option<Arc<EntitySnapshot>>,
+}
+
+/// Reflected copy of an entity subtree, taken right before it was despawned
+pub struct EntitySnapshot {
+    /// Root entity and all its descendants with every component registered in [`AppTypeRegistry`]
+    pub scene: DynamicScene,
+    /// Parent of the root entity at the moment of removal
+    pub parent: Option<Entity>,
+}
+
+impl RemovedEntity {
+    /// Create change without snapshot
+    pub const fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            snapshot: None,
+        }
+    }
+
+    /// Create change with snapshot of the entity and all its descendants.
+    /// Must be called before the entity is despawned
+    pub fn snapshot(world: &World, entity: Entity) -> Self {
+        let mut entities = vec![];
+        let mut stack = vec![entity];
+        while let Some(e) = stack.pop() {
+            entities.push(e);
+            if let Some(children) = world.get::<Children>(e) {
+                stack.extend(children.iter());
+            }
+        }
+
+        let mut scene = DynamicSceneBuilder::from_world(world)
+            .extract_entities(entities.into_iter())
+            .build();
+
+        //Parent of the root is not a part of snapshot, so it will be attached on revert
+        if let Some(root) = scene.entities.iter_mut().find(|e| e.entity == entity) {
+            root.components.retain(|c| !c.represents::<Parent>());
+        }
+
+        Self {
+            entity,
+            snapshot: Some(Arc::new(EntitySnapshot {
+                scene,
+                parent: world.get::<Parent>(entity).map(|p| p.get()),
+            })),
+        }
+    }
 }
 
 impl EditorChange for RemovedEntity {
@@ -295,37 +898,126 @@ impl EditorChange for RemovedEntity {
         world: &mut World,
         remap: &HashMap<Entity, Entity>,
     ) -> Result<ChangeResult, String> {
-        if let Some(e) = remap.get(&self.entity) {
-            if world.get_entity(*e).is_none() {
-                let id = world
-                    .spawn_empty()
-                    .insert((UndoMarker, OneFrameUndoIgnore::default()))
-                    .id();
-                info!("Reverted Removed Entity: {}", e.index());
-                Ok(ChangeResult::SuccessWithRemap(vec![(self.entity, id)]))
-            } else {
-                info!("Reverted Removed Entity: {}", e.index());
-                Ok(ChangeResult::Success)
+        let Some(snapshot) = &self.snapshot else {
+            if let Some(e) = remap.get(&self.entity) {
+                if world.get_entity(*e).is_some() {
+                    info!("Reverted Removed Entity: {}", e.index());
+                    return Ok(ChangeResult::Success);
+                }
             }
-        } else {
             let id = world
                 .spawn_empty()
                 .insert((UndoMarker, OneFrameUndoIgnore::default()))
                 .id();
             info!("Reverted Removed Entity: {}", self.entity.index());
-            Ok(ChangeResult::SuccessWithRemap(vec![(self.entity, id)]))
+            return Ok(ChangeResult::SuccessWithRemap(vec![(self.entity, id)]));
+        };
+
+        //Entities already restored by other changes will receive snapshot components
+        let mut entity_map = EntityHashMap::default();
+        for scene_entity in snapshot.scene.entities.iter() {
+            if let Some(e) = remap.get(&scene_entity.entity) {
+                if world.get_entity(*e).is_some() {
+                    entity_map.insert(scene_entity.entity, *e);
+                }
+            }
+        }
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        snapshot
+            .scene
+            .write_to_world_with(world, &mut entity_map, &type_registry)
+            .map_err(|err| format!("Failed to restore removed entity: {}", err))?;
+
+        for (_, e) in entity_map.iter() {
+            world
+                .entity_mut(*e)
+                .insert((UndoMarker, OneFrameUndoIgnore::default()));
+        }
+
+        if let (Some(parent), Some(root)) = (snapshot.parent, entity_map.get(&self.entity)) {
+            let parent = get_entity_with_remap(parent, remap);
+            if let Some(mut parent) = world.get_entity_mut(parent) {
+                parent.add_child(*root);
+            }
         }
+
+        info!(
+            "Reverted Removed Entity: {} with {} entities",
+            self.entity.index(),
+            entity_map.len()
+        );
+        Ok(ChangeResult::SuccessWithRemap(
+            entity_map.into_iter().collect(),
+        ))
     }
 
     fn debug_text(&self) -> String {
         format!("Removed Entity: {}", self.entity.index())
     }
 
+    fn approximate_size(&self) -> usize {
+        let snapshot_size = self.snapshot.as_ref().map_or(0, |snapshot| {
+            snapshot
+                .scene
+                .entities
+                .iter()
+                .flat_map(|entity| entity.components.iter())
+                .map(|component| approximate_reflect_size(component.as_ref()))
+                .sum()
+        });
+        std::mem::size_of::<Self>() + snapshot_size
+    }
+
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
         Arc::new(AddedEntity {
             entity: self.entity,
         })
     }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        let (parent, scene) = match &self.snapshot {
+            Some(snapshot) => (
+                snapshot.parent.map(|parent| store_entity(parent, ids)),
+                Some(store_scene(&snapshot.scene, registry, ids)?),
+            ),
+            None => (None, None),
+        };
+        Some(
+            StoredChange::RemovedEntity {
+                entity: store_entity(self.entity, ids),
+                parent,
+                scene,
+            }
+            .into(),
+        )
+    }
+}
+
+/// Extension for [`EntityCommands`] to despawn entity with all its descendants
+/// and register [`RemovedEntity`] change with full snapshot
+pub trait UndoDespawnExt {
+    fn despawn_recursive_with_undo(self);
+}
+
+impl UndoDespawnExt for EntityCommands<'_> {
+    fn despawn_recursive_with_undo(mut self) {
+        self.add(despawn_recursive_with_undo);
+    }
+}
+
+fn despawn_recursive_with_undo(entity: Entity, world: &mut World) {
+    if world.get_entity(entity).is_none() {
+        return;
+    }
+    let change = RemovedEntity::snapshot(world, entity);
+    world.entity_mut(entity).despawn_recursive();
+    world.send_event(NewChange {
+        change: Arc::new(change),
+    });
 }
 
 pub struct ComponentChange<T: Component> {
@@ -342,8 +1034,7 @@ impl<T: Component + Clone> EditorChange for ComponentChange<T> {
     ) -> Result<ChangeResult, String> {
         let e = get_entity_with_remap(self.entity, entity_remap);
 
-        world
-            .entity_mut(e)
+        get_entity_mut_or_err(world, e)?
             .insert((self.old_value.clone(), OneFrameUndoIgnore::default()));
         info!("Reverted ComponentChange for entity: {}", e.index());
         Ok(ChangeResult::Success)
@@ -366,6 +1057,7 @@ pub struct ReflectedComponentChange<T: Component + Reflect + FromReflect> {
     old_value: T,
     new_value: T,
     entity: Entity,
+    coalescing: UndoCoalescing,
 }
 
 impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedComponentChange<T> {
@@ -376,10 +1068,8 @@ impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedComponentCh
     ) -> Result<ChangeResult, String> {
         let e = get_entity_with_remap(self.entity, entity_remap);
 
-        world.entity_mut(e).insert((
-            <T as FromReflect>::from_reflect(&self.old_value).unwrap(),
-            OneFrameUndoIgnore::default(),
-        ));
+        let old_value = from_reflect_or_err::<T>(&self.old_value)?;
+        get_entity_mut_or_err(world, e)?.insert((old_value, OneFrameUndoIgnore::default()));
         world.send_event(UndoRedoApplied::<T> {
             entity: e,
             _phantom: std::marker::PhantomData,
@@ -400,13 +1090,59 @@ impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedComponentCh
         )
     }
 
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of::<Entity>()
+            + approximate_reflect_size(&self.old_value)
+            + approximate_reflect_size(&self.new_value)
+    }
+
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
         Arc::new(Self {
             old_value: <T as FromReflect>::from_reflect(&self.new_value).unwrap(),
             new_value: <T as FromReflect>::from_reflect(&self.old_value).unwrap(),
             entity: self.entity,
+            coalescing: self.coalescing,
         })
     }
+
+    fn coalesce(
+        &self,
+        next: &(dyn EditorChange + Send + Sync),
+        elapsed: Duration,
+    ) -> Option<Arc<dyn EditorChange + Send + Sync>> {
+        let UndoCoalescing::Window(window) = self.coalescing else {
+            return None;
+        };
+        let next = next.as_any()?.downcast_ref::<Self>()?;
+        if elapsed > window || next.entity != self.entity {
+            return None;
+        }
+        Some(Arc::new(Self {
+            old_value: <T as FromReflect>::from_reflect(&self.old_value)?,
+            new_value: <T as FromReflect>::from_reflect(&next.new_value)?,
+            entity: self.entity,
+            coalescing: self.coalescing,
+        }))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        Some(
+            StoredChange::ComponentChange {
+                entity: store_entity(self.entity, ids),
+                old_value: store_value(&self.old_value, registry, ids)?,
+                new_value: store_value(&self.new_value, registry, ids)?,
+            }
+            .into(),
+        )
+    }
 }
 
 pub struct AddedComponent<T: Component> {
@@ -488,12 +1224,30 @@ impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedAddedCompon
         format!("ReflectedAddedComponent for entity {:?}", self.entity)
     }
 
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of::<Entity>() + approximate_reflect_size(&self.new_value)
+    }
+
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
         Arc::new(ReflectedRemovedComponent {
             old_value: <T as FromReflect>::from_reflect(&self.new_value).unwrap(),
             entity: self.entity,
         })
     }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        Some(
+            StoredChange::AddedComponent {
+                entity: store_entity(self.entity, ids),
+                value: store_value(&self.new_value, registry, ids)?,
+            }
+            .into(),
+        )
+    }
 }
 
 pub struct RemovedComponent<T: Component + Clone> {
@@ -521,8 +1275,7 @@ impl<T: Component + Clone> EditorChange for RemovedComponent<T> {
             |remapped| *remapped,
         );
 
-        world
-            .entity_mut(dst)
+        get_entity_mut_or_err(world, dst)?
             .insert((self.old_value.clone(), OneFrameUndoIgnore::default()));
 
         info!("Reverted RemovedComponent for entity: {}", dst.index());
@@ -567,10 +1320,8 @@ impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedRemovedComp
             |remapped| *remapped,
         );
 
-        world.entity_mut(dst).insert((
-            <T as FromReflect>::from_reflect(&self.old_value).unwrap(),
-            OneFrameUndoIgnore::default(),
-        ));
+        let old_value = from_reflect_or_err::<T>(&self.old_value)?;
+        get_entity_mut_or_err(world, dst)?.insert((old_value, OneFrameUndoIgnore::default()));
         world.send_event(UndoRedoApplied::<T> {
             entity: dst,
             _phantom: std::marker::PhantomData,
@@ -588,16 +1339,93 @@ impl<T: Component + Reflect + FromReflect> EditorChange for ReflectedRemovedComp
         format!("ReflectedRemovedComponent for entity {:?}", self.entity)
     }
 
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of::<Entity>() + approximate_reflect_size(&self.old_value)
+    }
+
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
         Arc::new(ReflectedAddedComponent {
             new_value: <T as FromReflect>::from_reflect(&self.old_value).unwrap(),
             entity: self.entity,
         })
     }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        Some(
+            StoredChange::RemovedComponent {
+                entity: store_entity(self.entity, ids),
+                value: store_value(&self.old_value, registry, ids)?,
+            }
+            .into(),
+        )
+    }
+}
+
+pub struct ReflectedResourceChange<R: Resource + Reflect + FromReflect> {
+    old_value: R,
+    new_value: R,
+}
+
+impl<R: Resource + Reflect + FromReflect> EditorChange for ReflectedResourceChange<R> {
+    fn revert(
+        &self,
+        world: &mut World,
+        _entity_remap: &HashMap<Entity, Entity>,
+    ) -> Result<ChangeResult, String> {
+        let old_value = from_reflect_or_err::<R>(&self.old_value)?;
+        world.insert_resource(old_value);
+        if let Some(mut storage) = world.get_resource_mut::<AutoUndoResourceStorage<R>>() {
+            storage.ignore_change = true;
+        }
+
+        info!(
+            "Reverted ReflectedResourceChange for {}",
+            pretty_type_name::pretty_type_name::<R>()
+        );
+        Ok(ChangeResult::Success)
+    }
+
+    fn debug_text(&self) -> String {
+        format!(
+            "{:?} resource changed",
+            pretty_type_name::pretty_type_name::<R>()
+        )
+    }
+
+    fn approximate_size(&self) -> usize {
+        approximate_reflect_size(&self.old_value) + approximate_reflect_size(&self.new_value)
+    }
+
+    fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
+        Arc::new(Self {
+            old_value: <R as FromReflect>::from_reflect(&self.new_value).unwrap(),
+            new_value: <R as FromReflect>::from_reflect(&self.old_value).unwrap(),
+        })
+    }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        Some(
+            StoredChange::ResourceChange {
+                old_value: store_value(&self.old_value, registry, ids)?,
+                new_value: store_value(&self.new_value, registry, ids)?,
+            }
+            .into(),
+        )
+    }
 }
 
 pub struct ManyChanges {
     changes: Vec<Arc<dyn EditorChange + Send + Sync>>,
+    /// Name of the transaction which produced these changes
+    label: Option<String>,
 }
 
 impl EditorChange for ManyChanges {
@@ -607,25 +1435,57 @@ impl EditorChange for ManyChanges {
         entity_remap: &HashMap<Entity, Entity>,
     ) -> Result<ChangeResult, String> {
         let mut remap = entity_remap.clone();
-        for change in self.changes.iter() {
-            let res = change.revert(world, &remap)?;
-            match res {
-                ChangeResult::Success => {}
-                ChangeResult::SuccessWithRemap(new_remap) => {
-                    remap.extend(new_remap);
+        let mut errors = vec![];
+        let mut failed_count = 0;
+        //Changes are reverted from the last to the first one.
+        //Failed change does not stop others, so the group is applied as much as possible
+        for change in self.changes.iter().rev() {
+            match change.revert(world, &remap) {
+                Ok(ChangeResult::Success) => {}
+                Ok(ChangeResult::SuccessWithRemap(new_remap)) => {
+                    extend_remap(&mut remap, new_remap);
+                }
+                Ok(ChangeResult::PartialSuccess {
+                    remap: new_remap,
+                    errors: new_errors,
+                }) => {
+                    extend_remap(&mut remap, new_remap);
+                    errors.extend(new_errors);
+                }
+                Err(err) => {
+                    failed_count += 1;
+                    errors.push(format!("{}: {}", change.debug_text(), err));
                 }
             }
         }
 
+        if failed_count == self.changes.len() {
+            return Err(errors.join("\n"));
+        }
+
         info!("Reverted ManyChanges");
 
-        Ok(ChangeResult::SuccessWithRemap(
-            remap.iter().map(|(key, value)| (*key, *value)).collect(),
-        ))
+        let remap = remap.iter().map(|(key, value)| (*key, *value)).collect();
+        if errors.is_empty() {
+            Ok(ChangeResult::SuccessWithRemap(remap))
+        } else {
+            Ok(ChangeResult::PartialSuccess { remap, errors })
+        }
     }
 
     fn debug_text(&self) -> String {
-        "ManyChanges".to_string()
+        self.label
+            .clone()
+            .unwrap_or_else(|| "ManyChanges".to_string())
+    }
+
+    fn approximate_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .changes
+                .iter()
+                .map(|change| change.approximate_size())
+                .sum::<usize>()
     }
 
     fn get_inverse(&self) -> Arc<dyn EditorChange + Send + Sync> {
@@ -636,7 +1496,25 @@ impl EditorChange for ManyChanges {
             .map(|change| change.get_inverse())
             .collect::<Vec<_>>();
 
-        Arc::new(Self { changes })
+        Arc::new(Self {
+            changes,
+            label: self.label.clone(),
+        })
+    }
+
+    fn to_stored(
+        &self,
+        registry: &AppTypeRegistry,
+        ids: &StoredEntityIds,
+    ) -> Option<StoredChanges> {
+        let mut changes = vec![];
+        for change in self.changes.iter() {
+            changes.extend(change.to_stored(registry, ids)?.changes);
+        }
+        Some(StoredChanges {
+            label: self.label.clone(),
+            changes,
+        })
     }
 }
 
@@ -660,24 +1538,99 @@ pub struct UndoIgnoreStorage {
     pub storage: HashMap<Entity, OneFrameUndoIgnore>,
 }
 
+/// Resources which next change is not an undoable change, like resources restored by scene load.
+/// Resources are given by type id, so code which writes them does not need to know their types
+#[derive(Resource, Default)]
+pub struct UndoIgnoreResources {
+    pub types: HashSet<TypeId>,
+}
+
+impl UndoIgnoreResources {
+    /// Ignore writing of all resources of the scene to world
+    pub fn ignore_scene(&mut self, scene: &DynamicScene) {
+        self.types.extend(
+            scene
+                .resources
+                .iter()
+                .filter_map(|resource| resource.get_represented_type_info())
+                .map(|info| info.type_id()),
+        );
+    }
+}
+
 #[derive(Resource)]
 pub struct AutoUndoStorage<T: Component> {
     pub storage: HashMap<Entity, T>,
+    coalescing: UndoCoalescing,
 }
 
 impl<T: Component> Default for AutoUndoStorage<T> {
     fn default() -> Self {
         Self {
             storage: HashMap::new(),
+            coalescing: UndoCoalescing::default(),
+        }
+    }
+}
+
+/// Policy of merging consecutive changes of the same component on the same entity into one undo step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UndoCoalescing {
+    /// Every change is a separate undo step
+    #[default]
+    Disabled,
+    /// Change made within the window after the previous one is merged into it.
+    /// Merged change keeps the first old value and the last new value
+    Window(Duration),
+}
+
+/// Last known value of the resource with auto undo
+#[derive(Resource)]
+pub struct AutoUndoResourceStorage<R: Resource> {
+    pub value: Option<R>,
+    latency: Option<i32>,
+    /// Next change of the resource was made by undo/redo and will not be stored
+    ignore_change: bool,
+}
+
+impl<R: Resource> Default for AutoUndoResourceStorage<R> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            latency: None,
+            ignore_change: false,
         }
     }
 }
 
+/// Types of resources registered with [`AppAutoUndo::auto_undo_resource`]
+#[derive(Resource, Default, Clone)]
+pub struct AutoUndoResources {
+    pub types: HashSet<TypeId>,
+}
+
+impl AutoUndoResources {
+    /// Are changes of the resource undoable
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.types.contains(&type_id)
+    }
+}
+
 pub trait AppAutoUndo {
     fn auto_undo<T: Component + Clone>(&mut self) -> &mut Self;
 
     //Allow more complex undo and auto entity remapping
     fn auto_reflected_undo<T: Component + Reflect + FromReflect>(&mut self) -> &mut Self;
+
+    //Same as auto_reflected_undo, but consecutive changes are merged by the policy.
+    //Can be called for already registered type to change its policy
+    fn auto_reflected_undo_with_coalescing<T: Component + Reflect + FromReflect>(
+        &mut self,
+        coalescing: UndoCoalescing,
+    ) -> &mut Self;
+
+    //Undo for resource changes
+    fn auto_undo_resource<R: Resource + Reflect + FromReflect>(&mut self) -> &mut Self;
 }
 
 impl AppAutoUndo for App {
@@ -697,6 +1650,7 @@ impl AppAutoUndo for App {
                 auto_undo_remove_detect::<T>,
                 apply_deferred,
                 auto_undo_system_changed::<T>,
+                apply_deferred,
                 auto_undo_system::<T>,
             )
                 .chain()
@@ -707,12 +1661,18 @@ impl AppAutoUndo for App {
     }
 
     fn auto_reflected_undo<T: Component + Reflect + FromReflect>(&mut self) -> &mut Self {
-        if !self.world.contains_resource::<ChangeChain>() {
+        //Type can be registered several times, but systems must be added once
+        if !self.world.contains_resource::<ChangeChain>()
+            || self.world.contains_resource::<AutoUndoStorage<T>>()
+        {
             return self;
         }
 
         self.world.insert_resource(AutoUndoStorage::<T>::default());
         self.add_event::<UndoRedoApplied<T>>();
+        self.world
+            .get_resource_or_insert_with(StoredChangeRegistry::default)
+            .register_component::<T>();
 
         self.add_systems(
             PostUpdate,
@@ -722,6 +1682,7 @@ impl AppAutoUndo for App {
                 auto_undo_reflected_remove_detect::<T>,
                 apply_deferred,
                 auto_undo_system_changed::<T>,
+                apply_deferred,
                 auto_undo_reflected_system::<T>,
             )
                 .chain()
@@ -735,6 +1696,43 @@ impl AppAutoUndo for App {
 
         self
     }
+
+    fn auto_reflected_undo_with_coalescing<T: Component + Reflect + FromReflect>(
+        &mut self,
+        coalescing: UndoCoalescing,
+    ) -> &mut Self {
+        self.auto_reflected_undo::<T>();
+        if let Some(mut storage) = self.world.get_resource_mut::<AutoUndoStorage<T>>() {
+            storage.coalescing = coalescing;
+        }
+        self
+    }
+
+    fn auto_undo_resource<R: Resource + Reflect + FromReflect>(&mut self) -> &mut Self {
+        //Resource can be registered several times, but its system must be added once
+        if !self.world.contains_resource::<ChangeChain>()
+            || self.world.contains_resource::<AutoUndoResourceStorage<R>>()
+        {
+            return self;
+        }
+
+        self.world
+            .insert_resource(AutoUndoResourceStorage::<R>::default());
+        self.world
+            .get_resource_or_insert_with(AutoUndoResources::default)
+            .types
+            .insert(TypeId::of::<R>());
+        self.world
+            .get_resource_or_insert_with(StoredChangeRegistry::default)
+            .register_resource::<R>();
+
+        self.add_systems(
+            PostUpdate,
+            auto_undo_resource_system::<R>.in_set(UndoSet::PerType),
+        );
+
+        self
+    }
 }
 
 fn apply_for_every_typed_field<D: Reflect>(
@@ -817,6 +1815,44 @@ fn apply_for_every_typed_field<D: Reflect>(
     }
 }
 
+/// Approximate memory used by reflected value in bytes, including content of collections and strings
+pub fn approximate_reflect_size(value: &dyn Reflect) -> usize {
+    approximate_reflect_size_recursive(value, MAX_REFLECT_RECURSION)
+}
+
+fn approximate_reflect_size_recursive(value: &dyn Reflect, max_recursion: i32) -> usize {
+    if max_recursion < 0 {
+        return std::mem::size_of_val(value);
+    }
+    let size_of =
+        |value: &dyn Reflect| approximate_reflect_size_recursive(value, max_recursion - 1);
+    match value.reflect_ref() {
+        bevy::reflect::ReflectRef::Struct(s) => s.iter_fields().map(size_of).sum(),
+        bevy::reflect::ReflectRef::TupleStruct(s) => s.iter_fields().map(size_of).sum(),
+        bevy::reflect::ReflectRef::Tuple(s) => s.iter_fields().map(size_of).sum(),
+        bevy::reflect::ReflectRef::List(s) => {
+            std::mem::size_of::<Vec<u8>>() + s.iter().map(size_of).sum::<usize>()
+        }
+        bevy::reflect::ReflectRef::Array(s) => s.iter().map(size_of).sum(),
+        bevy::reflect::ReflectRef::Map(s) => {
+            std::mem::size_of::<HashMap<u8, u8>>()
+                + s.iter()
+                    .map(|(key, value)| size_of(key) + size_of(value))
+                    .sum::<usize>()
+        }
+        bevy::reflect::ReflectRef::Enum(s) => {
+            std::mem::size_of::<usize>()
+                + s.iter_fields()
+                    .map(|field| size_of(field.value()))
+                    .sum::<usize>()
+        }
+        bevy::reflect::ReflectRef::Value(v) => v.downcast_ref::<String>().map_or_else(
+            || std::mem::size_of_val(v),
+            |s| std::mem::size_of::<String>() + s.capacity(),
+        ),
+    }
+}
+
 fn auto_remap_undo_redo<T: Component + Reflect>(
     change_chain: Res<ChangeChain>,
     mut query: Query<&mut T>,
@@ -974,11 +2010,13 @@ fn auto_undo_system_changed<T: Component>(
 fn auto_undo_system<T: Component + Clone>(
     mut commands: Commands,
     mut storage: ResMut<AutoUndoStorage<T>>,
+    change_chain: Res<ChangeChain>,
     mut query: Query<(Entity, &mut T), With<ChangedMarker<T>>>,
     mut new_change: EventWriter<NewChange>,
 ) {
+    let flush = change_chain.is_transaction_closing();
     for (e, data) in query.iter_mut() {
-        if !data.is_changed() {
+        if flush || !data.is_changed() {
             commands.entity(e).remove::<ChangedMarker<T>>();
 
             if let Some(prev_value) = storage.storage.get(&e) {
@@ -1000,13 +2038,16 @@ fn auto_undo_system<T: Component + Clone>(
 fn auto_undo_reflected_system<T: Component + Reflect + FromReflect>(
     mut commands: Commands,
     mut storage: ResMut<AutoUndoStorage<T>>,
+    change_chain: Res<ChangeChain>,
     mut query: Query<(Entity, &mut T, &mut ChangedMarker<T>)>,
     mut new_change: EventWriter<NewChange>,
 ) {
+    //Closing transaction must receive all delayed changes without waiting for latency
+    let flush = change_chain.is_transaction_closing();
     for (e, data, mut marker) in query.iter_mut() {
-        if !data.is_changed() {
+        if flush || !data.is_changed() {
             marker.latency -= 1;
-            if marker.latency > 0 {
+            if !flush && marker.latency > 0 {
                 continue;
             }
 
@@ -1018,6 +2059,7 @@ fn auto_undo_reflected_system<T: Component + Reflect + FromReflect>(
                         old_value: <T as FromReflect>::from_reflect(prev_value).unwrap(),
                         new_value: <T as FromReflect>::from_reflect(data.as_ref()).unwrap(),
                         entity: e,
+                        coalescing: storage.coalescing,
                     }),
                 });
                 info!("Auto undo change for entity {:?}", e);
@@ -1031,3 +2073,57 @@ fn auto_undo_reflected_system<T: Component + Reflect + FromReflect>(
         }
     }
 }
+
+fn auto_undo_resource_system<R: Resource + Reflect + FromReflect>(
+    mut storage: ResMut<AutoUndoResourceStorage<R>>,
+    change_chain: Res<ChangeChain>,
+    resource: Option<Res<R>>,
+    ignore_resources: Option<ResMut<UndoIgnoreResources>>,
+    mut new_change: EventWriter<NewChange>,
+) {
+    let Some(resource) = resource else {
+        return;
+    };
+
+    let ignored = ignore_resources
+        .is_some_and(|mut ignore_resources| ignore_resources.types.remove(&TypeId::of::<R>()));
+    if storage.value.is_none() || storage.ignore_change || ignored {
+        storage.value = <R as FromReflect>::from_reflect(resource.as_ref());
+        storage.latency = None;
+        storage.ignore_change = false;
+        return;
+    }
+
+    let flush = change_chain.is_transaction_closing();
+    if resource.is_changed() {
+        storage.latency = Some(AUTO_UNDO_LATENCY);
+    }
+    let Some(latency) = storage.latency.as_mut() else {
+        return;
+    };
+    *latency -= 1;
+    if !flush && (*latency > 0 || resource.is_changed()) {
+        return;
+    }
+    storage.latency = None;
+
+    let Some(new_value) = <R as FromReflect>::from_reflect(resource.as_ref()) else {
+        return;
+    };
+    if let Some(prev_value) = storage.value.take() {
+        //Resource could be marked as changed without real changes
+        if prev_value.reflect_partial_eq(&new_value) != Some(true) {
+            new_change.send(NewChange {
+                change: Arc::new(ReflectedResourceChange {
+                    old_value: prev_value,
+                    new_value: <R as FromReflect>::from_reflect(&new_value).unwrap(),
+                }),
+            });
+            info!(
+                "Auto undo change for resource {}",
+                pretty_type_name::pretty_type_name::<R>()
+            );
+        }
+    }
+    storage.value = Some(new_value);
+}
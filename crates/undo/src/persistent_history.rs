@@ -0,0 +1,620 @@
+//! Undo history which is saved next to the scene file and restored when the same scene is opened again.
+//! Only reflected changes can be stored. Entities of the scene are stored by their ids in the saved scene file,
+//! which are given by the save as a map from world entities, see [`store_history`]
+use std::{
+    any::TypeId,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    sync::Arc,
+    time::Duration,
+};
+
+use bevy::{
+    ecs::entity::{Entities, EntityHashMap},
+    prelude::*,
+    reflect::TypeRegistry,
+    scene::{
+        serde::{SceneDeserializer, SceneSerializer},
+        DynamicEntity,
+    },
+    utils::{HashMap, HashSet},
+};
+use serde::de::DeserializeSeed;
+use space_persistence::{
+    deserialize_reflect, load_reflect_file, save_reflect_file, serialize_reflect,
+};
+
+use crate::{
+    apply_for_every_typed_field, get_entity_with_remap, AddedEntity, ChangeChain, ChangeId,
+    EditorChange, EntitySnapshot, HistoryNode, ManyChanges, OneFrameUndoIgnore,
+    ReflectedAddedComponent, ReflectedComponentChange, ReflectedRemovedComponent,
+    ReflectedResourceChange, RemovedEntity, UndoCoalescing, MAX_REFLECT_RECURSION,
+};
+
+type BoxedChange = Arc<dyn EditorChange + Send + Sync>;
+
+/// Serializable form of [`ChangeChain`]
+#[derive(Reflect, Default, Debug)]
+pub struct StoredHistory {
+    /// Scene entities referenced by history. All of them must exist in the loaded scene
+    pub scene_entities: Vec<u64>,
+    /// Parent node is always stored before its children
+    pub nodes: Vec<StoredHistoryNode>,
+    /// Index of the current node in `nodes`
+    pub current: Option<usize>,
+}
+
+#[derive(Reflect, Debug)]
+pub struct StoredHistoryNode {
+    /// Index of the parent node in [`StoredHistory::nodes`]
+    pub parent: Option<usize>,
+    pub changes: StoredChanges,
+}
+
+/// Serializable form of one undo step. Nested groups are flattened,
+/// it does not change the result of revert because grouped changes are reverted in reverse order
+#[derive(Reflect, Default, Debug, Clone)]
+pub struct StoredChanges {
+    /// Label of [`ManyChanges`]. Step without label with one change is restored as that change
+    pub label: Option<String>,
+    pub changes: Vec<StoredChange>,
+}
+
+impl From<StoredChange> for StoredChanges {
+    fn from(change: StoredChange) -> Self {
+        Self {
+            label: None,
+            changes: vec![change],
+        }
+    }
+}
+
+/// Serializable form of one change. Entities are stored as bits of their [`StoredEntityIds`] id,
+/// reflected values are stored as ron strings with type path
+#[derive(Reflect, Debug, Clone)]
+pub enum StoredChange {
+    AddedEntity {
+        entity: u64,
+    },
+    RemovedEntity {
+        entity: u64,
+        parent: Option<u64>,
+        /// Serialized [`EntitySnapshot`] scene
+        scene: Option<String>,
+    },
+    ComponentChange {
+        entity: u64,
+        old_value: String,
+        new_value: String,
+    },
+    AddedComponent {
+        entity: u64,
+        value: String,
+    },
+    RemovedComponent {
+        entity: u64,
+        value: String,
+    },
+    ResourceChange {
+        old_value: String,
+        new_value: String,
+    },
+}
+
+impl StoredChange {
+    fn collect_entities(&self, entities: &mut Vec<u64>) {
+        match self {
+            Self::AddedEntity { entity }
+            | Self::ComponentChange { entity, .. }
+            | Self::AddedComponent { entity, .. }
+            | Self::RemovedComponent { entity, .. } => entities.push(*entity),
+            Self::RemovedEntity { entity, parent, .. } => {
+                entities.push(*entity);
+                entities.extend(parent);
+            }
+            Self::ResourceChange { .. } => {}
+        }
+    }
+}
+
+pub(crate) fn register_types(app: &mut App) {
+    app.register_type::<StoredHistory>()
+        .register_type::<StoredHistoryNode>()
+        .register_type::<StoredChanges>()
+        .register_type::<StoredChange>()
+        .register_type::<Vec<StoredHistoryNode>>()
+        .register_type::<Vec<StoredChange>>()
+        .register_type::<Vec<u64>>()
+        .register_type::<Option<u64>>()
+        .register_type::<Option<usize>>()
+        .register_type::<Option<String>>();
+}
+
+type RestoreComponentChange = fn(Entity, &dyn Reflect, &dyn Reflect) -> Option<BoxedChange>;
+type RestoreComponentValue = fn(Entity, &dyn Reflect) -> Option<BoxedChange>;
+type RestoreResourceChange = fn(&dyn Reflect, &dyn Reflect) -> Option<BoxedChange>;
+
+#[derive(Clone, Copy)]
+struct ComponentRestoreFns {
+    changed: RestoreComponentChange,
+    added: RestoreComponentValue,
+    removed: RestoreComponentValue,
+}
+
+/// Constructors of typed changes from stored reflected values.
+/// Types are registered by [`crate::AppAutoUndo::auto_reflected_undo`] and [`crate::AppAutoUndo::auto_undo_resource`]
+#[derive(Resource, Default, Clone)]
+pub struct StoredChangeRegistry {
+    components: HashMap<TypeId, ComponentRestoreFns>,
+    resources: HashMap<TypeId, RestoreResourceChange>,
+}
+
+impl StoredChangeRegistry {
+    pub fn register_component<T: Component + Reflect + FromReflect>(&mut self) {
+        self.components.insert(
+            TypeId::of::<T>(),
+            ComponentRestoreFns {
+                changed: |entity, old_value, new_value| {
+                    Some(Arc::new(ReflectedComponentChange::<T> {
+                        old_value: T::from_reflect(old_value)?,
+                        new_value: T::from_reflect(new_value)?,
+                        entity,
+                        coalescing: UndoCoalescing::Disabled,
+                    }))
+                },
+                added: |entity, value| {
+                    Some(Arc::new(ReflectedAddedComponent::<T> {
+                        new_value: T::from_reflect(value)?,
+                        entity,
+                    }))
+                },
+                removed: |entity, value| {
+                    Some(Arc::new(ReflectedRemovedComponent::<T> {
+                        old_value: T::from_reflect(value)?,
+                        entity,
+                    }))
+                },
+            },
+        );
+    }
+
+    pub fn register_resource<R: Resource + Reflect + FromReflect>(&mut self) {
+        self.resources
+            .insert(TypeId::of::<R>(), |old_value, new_value| {
+                Some(Arc::new(ReflectedResourceChange::<R> {
+                    old_value: R::from_reflect(old_value)?,
+                    new_value: R::from_reflect(new_value)?,
+                }))
+            });
+    }
+}
+
+/// Path of the undo history file for the scene file
+pub fn history_path(scene_path: &str) -> String {
+    let base = scene_path
+        .strip_suffix(".scn.ron")
+        .or_else(|| scene_path.strip_suffix(".scn.bin"))
+        .unwrap_or(scene_path);
+    format!("{}.undo.ron", base)
+}
+
+/// Save undo history made by [`store_history`] to file
+pub fn save_history_file(
+    path: &str,
+    history: &StoredHistory,
+    registry: &TypeRegistry,
+) -> Result<(), String> {
+    save_reflect_file(path, history, registry)
+}
+
+/// Read undo history file. Use [`restore_history`] to apply it after the scene is loaded
+pub fn load_history_file(world: &World, path: &str) -> Result<StoredHistory, String> {
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let value = load_reflect_file(path, &registry)?;
+    StoredHistory::from_reflect(value.as_ref())
+        .ok_or_else(|| format!("Wrong undo history format in {}", path))
+}
+
+/// Convert undo history to serializable form. `scene_ids` maps world entities to their ids in the saved
+/// scene file, so it must be done at the same moment as the scene is serialized
+pub fn store_history(world: &World, scene_ids: &EntityHashMap<Entity>) -> StoredHistory {
+    let change_chain = world.resource::<ChangeChain>();
+    let registry = world.resource::<AppTypeRegistry>();
+    let ids = StoredEntityIds::new(&change_chain.entity_remap, scene_ids);
+
+    let mut stored = change_chain
+        .history
+        .iter()
+        .map(|(id, node)| (*id, node.change.to_stored(registry, &ids)))
+        .collect::<HashMap<_, _>>();
+
+    //Changes before the change which can not be stored can not be undone after restore,
+    //so the state after it becomes history root
+    let base = change_chain
+        .ancestors(change_chain.current)
+        .into_iter()
+        .find(|id| stored[id].is_none());
+    let start = base.map_or_else(
+        || change_chain.roots.clone(),
+        |id| {
+            warn!(
+                "Undo history before \"{}\" can not be saved",
+                change_chain.history[&id].change.debug_text()
+            );
+            change_chain.history[&id].children.clone()
+        },
+    );
+
+    let mut history = StoredHistory::default();
+    let mut indices = HashMap::new();
+    let mut queue = VecDeque::from(start);
+    while let Some(id) = queue.pop_front() {
+        let node = &change_chain.history[&id];
+        //Branch which starts with change that can not be stored is unreachable
+        let Some(changes) = stored.get_mut(&id).and_then(Option::take) else {
+            continue;
+        };
+        indices.insert(id, history.nodes.len());
+        history.nodes.push(StoredHistoryNode {
+            parent: node.parent.and_then(|parent| indices.get(&parent).copied()),
+            changes,
+        });
+        queue.extend(node.children.iter().copied());
+    }
+    history.current = change_chain
+        .current
+        .and_then(|id| indices.get(&id).copied());
+
+    let mut entities = vec![];
+    for node in history.nodes.iter() {
+        for change in node.changes.changes.iter() {
+            change.collect_entities(&mut entities);
+        }
+    }
+    entities.sort_unstable();
+    entities.dedup();
+    history.scene_entities = entities
+        .into_iter()
+        .filter(|id| ids.is_scene_entity(*id))
+        .collect();
+
+    history
+}
+
+/// Ids of entities in stored history. Scene entities are stored by their ids in the scene file,
+/// other entities receive ids which never match an entity of the scene
+pub struct StoredEntityIds<'a> {
+    entity_remap: &'a HashMap<Entity, Entity>,
+    scene_ids: &'a EntityHashMap<Entity>,
+    file_ids: HashSet<Entity>,
+    other: RefCell<EntityHashMap<Entity>>,
+    next_other: Cell<u32>,
+}
+
+impl<'a> StoredEntityIds<'a> {
+    fn new(
+        entity_remap: &'a HashMap<Entity, Entity>,
+        scene_ids: &'a EntityHashMap<Entity>,
+    ) -> Self {
+        Self {
+            entity_remap,
+            scene_ids,
+            file_ids: scene_ids.values().copied().collect(),
+            other: RefCell::default(),
+            next_other: Cell::new(0),
+        }
+    }
+
+    /// Stored id of the entity with all undo remaps applied
+    fn get(&self, entity: Entity) -> Entity {
+        let entity = get_entity_with_remap(entity, self.entity_remap);
+        if let Some(id) = self.scene_ids.get(&entity) {
+            return *id;
+        }
+        *self
+            .other
+            .borrow_mut()
+            .entry(entity)
+            .or_insert_with(|| loop {
+                let id = Entity::from_raw(self.next_other.get());
+                self.next_other.set(self.next_other.get() + 1);
+                if !self.file_ids.contains(&id) {
+                    break id;
+                }
+            })
+    }
+
+    fn is_scene_entity(&self, id: u64) -> bool {
+        Entity::try_from_bits(id).is_ok_and(|entity| self.file_ids.contains(&entity))
+    }
+}
+
+/// Replace undo history with stored one. `scene_map` maps entities of the scene file to the loaded entities.
+/// Entities which are not in the scene receive new ids which never point to existing entity
+pub fn restore_history(
+    world: &mut World,
+    history: &StoredHistory,
+    scene_map: &EntityHashMap<Entity>,
+) -> Result<(), String> {
+    for id in history.scene_entities.iter() {
+        let entity = Entity::try_from_bits(*id).map_err(|err| err.to_string())?;
+        if !scene_map.contains_key(&entity) {
+            return Err(format!(
+                "Entity {:?} from undo history is missing in the scene",
+                entity
+            ));
+        }
+    }
+    for (idx, node) in history.nodes.iter().enumerate() {
+        if node.parent.is_some_and(|parent| parent >= idx) {
+            return Err("Undo history nodes are in the wrong order".to_string());
+        }
+    }
+    if history
+        .current
+        .is_some_and(|current| current >= history.nodes.len())
+    {
+        return Err("Current undo history node does not exist".to_string());
+    }
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let constructors = world.resource::<StoredChangeRegistry>().clone();
+    let (changes, placeholders) = {
+        let registry = type_registry.read();
+        let ctx = RestoreContext {
+            registry: &registry,
+            constructors: &constructors,
+            scene_map,
+            entities: world.entities(),
+            placeholders: RefCell::default(),
+        };
+        let changes = history
+            .nodes
+            .iter()
+            .map(|node| ctx.changes(&node.changes))
+            .collect::<Result<Vec<_>, _>>();
+        (changes, ctx.placeholders.into_inner())
+    };
+
+    //Reserved entities are spawned and despawned right away, so their ids are dead
+    for placeholder in placeholders.into_values() {
+        if let Some(entity) = world.get_or_spawn(placeholder) {
+            entity.despawn();
+        }
+    }
+    let changes = changes?;
+
+    let mut change_chain = ChangeChain::default();
+    for (idx, (node, change)) in history.nodes.iter().zip(changes).enumerate() {
+        let id = ChangeId(idx as u64);
+        let parent = node.parent.map(|parent| ChangeId(parent as u64));
+        let size = change.approximate_size();
+        change_chain.history.insert(
+            id,
+            HistoryNode {
+                change,
+                parent,
+                children: vec![],
+                redo_child: None,
+                quarantined: None,
+                size,
+                time: Duration::ZERO,
+            },
+        );
+        match parent.and_then(|parent| change_chain.history.get_mut(&parent)) {
+            Some(parent) => parent.children.push(id),
+            None => change_chain.roots.push(id),
+        }
+        change_chain.set_redo_target(parent, id);
+    }
+    change_chain.next_id = history.nodes.len() as u64;
+    change_chain.current = history.current.map(|current| ChangeId(current as u64));
+    for id in change_chain.ancestors(change_chain.current) {
+        let parent = change_chain.history[&id].parent;
+        change_chain.set_redo_target(parent, id);
+    }
+    change_chain.update_stacks();
+
+    //Loaded scene is the current state of restored history, not a new change
+    for entity in scene_map.values() {
+        if let Some(mut entity) = world.get_entity_mut(*entity) {
+            entity.insert(OneFrameUndoIgnore::default());
+        }
+    }
+
+    *world.resource_mut::<ChangeChain>() = change_chain;
+    Ok(())
+}
+
+struct RestoreContext<'a> {
+    registry: &'a TypeRegistry,
+    constructors: &'a StoredChangeRegistry,
+    scene_map: &'a EntityHashMap<Entity>,
+    entities: &'a Entities,
+    placeholders: RefCell<HashMap<Entity, Entity>>,
+}
+
+impl RestoreContext<'_> {
+    fn map_entity(&self, stored: Entity) -> Entity {
+        if let Some(entity) = self.scene_map.get(&stored) {
+            return *entity;
+        }
+        *self
+            .placeholders
+            .borrow_mut()
+            .entry(stored)
+            .or_insert_with(|| self.entities.reserve_entity())
+    }
+
+    fn entity(&self, id: u64) -> Result<Entity, String> {
+        Entity::try_from_bits(id)
+            .map(|entity| self.map_entity(entity))
+            .map_err(|err| err.to_string())
+    }
+
+    fn value(&self, data: &str) -> Result<Box<dyn Reflect>, String> {
+        let mut value = deserialize_reflect(data, self.registry)?;
+        map_value_entities(value.as_mut(), &|entity| self.map_entity(entity));
+        Ok(value)
+    }
+
+    fn component_fns(&self, value: &dyn Reflect) -> Result<ComponentRestoreFns, String> {
+        value
+            .get_represented_type_info()
+            .and_then(|info| self.constructors.components.get(&info.type_id()))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Component {} is not registered for undo",
+                    value.reflect_type_path()
+                )
+            })
+    }
+
+    fn changes(&self, stored: &StoredChanges) -> Result<BoxedChange, String> {
+        let mut changes = stored
+            .changes
+            .iter()
+            .map(|change| self.change(change))
+            .collect::<Result<Vec<_>, _>>()?;
+        if changes.len() == 1 && stored.label.is_none() {
+            Ok(changes.remove(0))
+        } else {
+            Ok(Arc::new(ManyChanges {
+                changes,
+                label: stored.label.clone(),
+            }))
+        }
+    }
+
+    fn change(&self, stored: &StoredChange) -> Result<BoxedChange, String> {
+        let change = match stored {
+            StoredChange::AddedEntity { entity } => Some(Arc::new(AddedEntity {
+                entity: self.entity(*entity)?,
+            }) as BoxedChange),
+            StoredChange::RemovedEntity {
+                entity,
+                parent,
+                scene,
+            } => {
+                let snapshot = match scene {
+                    Some(scene) => Some(Arc::new(EntitySnapshot {
+                        scene: self.scene(scene)?,
+                        parent: parent.map(|parent| self.entity(parent)).transpose()?,
+                    })),
+                    None => None,
+                };
+                Some(Arc::new(RemovedEntity {
+                    entity: self.entity(*entity)?,
+                    snapshot,
+                }) as BoxedChange)
+            }
+            StoredChange::ComponentChange {
+                entity,
+                old_value,
+                new_value,
+            } => {
+                let old_value = self.value(old_value)?;
+                let new_value = self.value(new_value)?;
+                (self.component_fns(old_value.as_ref())?.changed)(
+                    self.entity(*entity)?,
+                    old_value.as_ref(),
+                    new_value.as_ref(),
+                )
+            }
+            StoredChange::AddedComponent { entity, value } => {
+                let value = self.value(value)?;
+                (self.component_fns(value.as_ref())?.added)(self.entity(*entity)?, value.as_ref())
+            }
+            StoredChange::RemovedComponent { entity, value } => {
+                let value = self.value(value)?;
+                (self.component_fns(value.as_ref())?.removed)(self.entity(*entity)?, value.as_ref())
+            }
+            StoredChange::ResourceChange {
+                old_value,
+                new_value,
+            } => {
+                let old_value = self.value(old_value)?;
+                let new_value = self.value(new_value)?;
+                let restore = old_value
+                    .get_represented_type_info()
+                    .and_then(|info| self.constructors.resources.get(&info.type_id()))
+                    .ok_or_else(|| {
+                        format!(
+                            "Resource {} is not registered for undo",
+                            old_value.reflect_type_path()
+                        )
+                    })?;
+                restore(old_value.as_ref(), new_value.as_ref())
+            }
+        };
+        change.ok_or_else(|| format!("Failed to restore stored change {:?}", stored))
+    }
+
+    fn scene(&self, data: &str) -> Result<DynamicScene, String> {
+        let mut ron_deserializer =
+            ron::Deserializer::from_str(data).map_err(|err| err.to_string())?;
+        let scene = SceneDeserializer {
+            type_registry: self.registry,
+        }
+        .deserialize(&mut ron_deserializer)
+        .map_err(|err| err.to_string())?;
+        Ok(map_scene_entities(&scene, &|entity| {
+            self.map_entity(entity)
+        }))
+    }
+}
+
+/// Stored id of the entity, see [`StoredEntityIds`]
+pub(crate) fn store_entity(entity: Entity, ids: &StoredEntityIds) -> u64 {
+    ids.get(entity).to_bits()
+}
+
+pub(crate) fn store_value(
+    value: &dyn Reflect,
+    registry: &AppTypeRegistry,
+    ids: &StoredEntityIds,
+) -> Option<String> {
+    let mut value = value.clone_value();
+    map_value_entities(value.as_mut(), &|entity| ids.get(entity));
+    serialize_reflect(value.as_ref(), &registry.read()).ok()
+}
+
+pub(crate) fn store_scene(
+    scene: &DynamicScene,
+    registry: &AppTypeRegistry,
+    ids: &StoredEntityIds,
+) -> Option<String> {
+    let scene = map_scene_entities(scene, &|entity| ids.get(entity));
+    ron::to_string(&SceneSerializer::new(&scene, registry)).ok()
+}
+
+fn map_value_entities(value: &mut dyn Reflect, map: &dyn Fn(Entity) -> Entity) {
+    apply_for_every_typed_field::<Entity>(
+        value,
+        &|entity| *entity = map(*entity),
+        MAX_REFLECT_RECURSION,
+    );
+}
+
+fn map_scene_entities(scene: &DynamicScene, map: &dyn Fn(Entity) -> Entity) -> DynamicScene {
+    DynamicScene {
+        resources: vec![],
+        entities: scene
+            .entities
+            .iter()
+            .map(|scene_entity| DynamicEntity {
+                entity: map(scene_entity.entity),
+                components: scene_entity
+                    .components
+                    .iter()
+                    .map(|component| {
+                        let mut component = component.clone_value();
+                        map_value_entities(component.as_mut(), map);
+                        component
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
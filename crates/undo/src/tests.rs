@@ -1,4 +1,5 @@
 use super::*;
+use bevy::ecs::system::RunSystemOnce;
 
 #[cfg(test)]
 fn configure_app() -> App {
@@ -229,7 +230,7 @@ fn test_undo_with_remap() {
 
     app.world.entity_mut(test_id_1).despawn_recursive();
     app.world.send_event(NewChange {
-        change: Arc::new(RemovedEntity { entity: test_id_1 }),
+        change: Arc::new(RemovedEntity::new(test_id_1)),
     });
     repeat_update(&mut app, 2);
 
@@ -244,6 +245,553 @@ fn test_undo_with_remap() {
     assert!(query.get_single(&app.world).is_ok());
 }
 
+#[test]
+fn test_undo_removed_entity_snapshot() {
+    let mut app = configure_app();
+    app.add_plugins(HierarchyPlugin);
+    app.register_type::<Transform>();
+
+    let parent = app.world.spawn(UndoMarker).id();
+    let test_id_1 = app
+        .world
+        .spawn((
+            UndoMarker,
+            Name::new("root"),
+            Transform::from_xyz(1., 2., 3.),
+        ))
+        .id();
+    let test_id_2 = app.world.spawn((UndoMarker, Name::new("child"))).id();
+    app.world.entity_mut(parent).add_child(test_id_1);
+    app.world.entity_mut(test_id_1).add_child(test_id_2);
+    repeat_update(&mut app, 2);
+
+    app.world.run_system_once(move |mut commands: Commands| {
+        commands.entity(test_id_1).despawn_recursive_with_undo();
+    });
+    repeat_update(&mut app, 2);
+    assert!(app.world.get_entity(test_id_1).is_none());
+    assert!(app.world.get_entity(test_id_2).is_none());
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 1);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+
+    let children = app.world.get::<Children>(parent).unwrap();
+    assert_eq!(children.len(), 1);
+    let root = children[0];
+    assert_eq!(app.world.get::<Name>(root).unwrap().as_str(), "root");
+    assert_eq!(
+        app.world.get::<Transform>(root).unwrap().translation,
+        Vec3::new(1., 2., 3.)
+    );
+    let child = app.world.get::<Children>(root).unwrap()[0];
+    assert_eq!(app.world.get::<Name>(child).unwrap().as_str(), "child");
+    assert_eq!(app.world.get::<Parent>(child).unwrap().get(), root);
+
+    let remap = &app.world.resource::<ChangeChain>().entity_remap;
+    assert_eq!(remap.get(&test_id_1), Some(&root));
+    assert_eq!(remap.get(&test_id_2), Some(&child));
+
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 2);
+    assert!(app.world.get_entity(root).is_none());
+    assert!(app.world.get_entity(child).is_none());
+    assert_eq!(app.world.get::<Children>(parent).map_or(0, |c| c.len()), 0);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+    let root = app.world.get::<Children>(parent).unwrap()[0];
+    assert_eq!(app.world.get::<Name>(root).unwrap().as_str(), "root");
+    assert_eq!(
+        app.world
+            .resource::<ChangeChain>()
+            .entity_remap
+            .get(&test_id_1),
+        Some(&root)
+    );
+}
+
+#[test]
+fn test_transaction_commit() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<Transform>();
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 10);
+    let changes_before = app.world.resource::<ChangeChain>().changes.len();
+
+    app.world
+        .resource_mut::<ChangeChain>()
+        .begin_transaction("Move entity");
+    for i in 1..=3 {
+        app.world.get_mut::<Transform>(test_id).unwrap().translation = Vec3::X * i as f32;
+        repeat_update(&mut app, 10);
+    }
+    app.world.get_mut::<Transform>(test_id).unwrap().translation = Vec3::Y;
+    app.world.resource_mut::<ChangeChain>().commit_transaction();
+    app.update();
+
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert!(change_chain.transaction().is_none());
+    assert_eq!(change_chain.changes.len(), changes_before + 1);
+    assert_eq!(
+        change_chain.changes.last().unwrap().debug_text(),
+        "Move entity"
+    );
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::ZERO
+    );
+
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::Y
+    );
+}
+
+#[test]
+fn test_transaction_cancel() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<Transform>();
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 10);
+    let changes_before = app.world.resource::<ChangeChain>().changes.len();
+
+    app.world.run_system_once(|mut commands: Commands| {
+        commands.begin_undo_transaction("Move entity");
+    });
+    app.world.get_mut::<Transform>(test_id).unwrap().translation = Vec3::X;
+    repeat_update(&mut app, 10);
+    assert_eq!(
+        app.world
+            .resource::<ChangeChain>()
+            .transaction()
+            .unwrap()
+            .changes
+            .len(),
+        1
+    );
+
+    app.world.run_system_once(|mut commands: Commands| {
+        commands.cancel_undo_transaction();
+    });
+    repeat_update(&mut app, 10);
+
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::ZERO
+    );
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert!(change_chain.transaction().is_none());
+    assert_eq!(change_chain.changes.len(), changes_before);
+}
+
+fn set_translation(app: &mut App, entity: Entity, translation: Vec3) {
+    app.world.get_mut::<Transform>(entity).unwrap().translation = translation;
+    repeat_update(app, 10);
+}
+
+#[test]
+fn test_undo_tree_branches() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<Transform>();
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 10);
+
+    set_translation(&mut app, test_id, Vec3::X);
+    let branch_point = app.world.resource::<ChangeChain>().current();
+    set_translation(&mut app, test_id, Vec3::Y);
+    let first_branch = app.world.resource::<ChangeChain>().current();
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    set_translation(&mut app, test_id, Vec3::Z);
+
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert_eq!(change_chain.history().len(), 4);
+    assert!(change_chain.changes_for_redo.is_empty());
+    let children = &change_chain.history()[&branch_point.unwrap()].children;
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0], first_branch.unwrap());
+
+    app.world.send_event(UndoRedo::JumpTo(first_branch));
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::Y
+    );
+    assert_eq!(app.world.resource::<ChangeChain>().current(), first_branch);
+
+    app.world.send_event(UndoRedo::JumpTo(branch_point));
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::X
+    );
+
+    //Redo follows the last visited branch
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::Y
+    );
+}
+
+#[test]
+fn test_undo_tree_trim() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<Transform>();
+    app.world
+        .resource_mut::<ChangeChainSettings>()
+        .max_change_chain_size = 2;
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 10);
+
+    set_translation(&mut app, test_id, Vec3::X);
+    set_translation(&mut app, test_id, Vec3::Y);
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    set_translation(&mut app, test_id, Vec3::Z);
+
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert_eq!(change_chain.history().len(), 2);
+    assert_eq!(change_chain.history_roots().len(), 2);
+    assert_eq!(change_chain.changes.len(), 1);
+
+    app.world.send_event(UndoRedo::JumpTo(None));
+    repeat_update(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::X
+    );
+}
+
+#[derive(Component, Reflect, Default, Clone)]
+#[reflect(Component)]
+struct TestText(String);
+
+#[test]
+fn test_undo_memory_budget() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<TestText>();
+    app.world
+        .resource_mut::<ChangeChainSettings>()
+        .max_memory_usage = 5000;
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, TestText::default())).id();
+    repeat_update(&mut app, 12);
+
+    for c in ['a', 'b', 'c'] {
+        app.world.get_mut::<TestText>(test_id).unwrap().0 = c.to_string().repeat(1000);
+        repeat_update(&mut app, 10);
+    }
+
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert_eq!(change_chain.changes.len(), 2);
+    assert!(change_chain.memory_usage() > 4000);
+    assert!(change_chain.memory_usage() <= 5000);
+    assert!(approximate_reflect_size(&TestText("a".repeat(1000))) >= 1000);
+}
+
+#[test]
+fn test_undo_coalescing() {
+    let mut app = configure_app();
+    app.auto_reflected_undo::<Transform>();
+    app.auto_reflected_undo_with_coalescing::<Transform>(UndoCoalescing::Window(
+        Duration::from_secs(3600),
+    ));
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    let other_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 12);
+    let initial_len = app.world.resource::<ChangeChain>().changes.len();
+
+    set_translation(&mut app, test_id, Vec3::X);
+    set_translation(&mut app, test_id, Vec3::Y);
+    set_translation(&mut app, test_id, Vec3::Z);
+    assert_eq!(
+        app.world.resource::<ChangeChain>().changes.len(),
+        initial_len + 1
+    );
+
+    //Other entity is a separate step and it ends coalescing of the first one
+    set_translation(&mut app, other_id, Vec3::X);
+    set_translation(&mut app, test_id, Vec3::ONE);
+    assert_eq!(
+        app.world.resource::<ChangeChain>().changes.len(),
+        initial_len + 3
+    );
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::ZERO
+    );
+
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 12);
+    assert_eq!(
+        app.world.get::<Transform>(test_id).unwrap().translation,
+        Vec3::Z
+    );
+}
+
+#[test]
+fn test_undo_coalescing_outside_window() {
+    let mut app = configure_app();
+    app.auto_reflected_undo_with_coalescing::<Transform>(UndoCoalescing::Window(Duration::ZERO));
+    app.update();
+
+    let test_id = app.world.spawn((UndoMarker, Transform::default())).id();
+    repeat_update(&mut app, 12);
+    let initial_len = app.world.resource::<ChangeChain>().changes.len();
+
+    set_translation(&mut app, test_id, Vec3::X);
+    set_translation(&mut app, test_id, Vec3::Y);
+    assert_eq!(
+        app.world.resource::<ChangeChain>().changes.len(),
+        initial_len + 2
+    );
+}
+
+#[test]
+fn test_failed_undo_is_quarantined() {
+    let mut app = configure_app();
+    app.update();
+
+    let test_id_1 = app.world.spawn_empty().id();
+    app.world.send_event(NewChange {
+        change: Arc::new(AddedEntity { entity: test_id_1 }),
+    });
+    repeat_update(&mut app, 2);
+    let test_id_2 = app.world.spawn_empty().id();
+    app.world.send_event(NewChange {
+        change: Arc::new(AddedEntity { entity: test_id_2 }),
+    });
+    repeat_update(&mut app, 2);
+    let failed_change = app.world.resource::<ChangeChain>().current().unwrap();
+
+    //Entity was despawned outside of the undo system
+    app.world.despawn(test_id_2);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert!(change_chain.history()[&failed_change].quarantined.is_some());
+    assert_eq!(change_chain.changes.len(), 1);
+    let failed_events = app.world.resource::<Events<UndoRedoFailed>>();
+    assert_eq!(failed_events.get_reader().read(failed_events).count(), 1);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+    assert!(app.world.get_entity(test_id_1).is_none());
+
+    app.world.send_event(UndoRedo::Redo);
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 2);
+    let change_chain = app.world.resource::<ChangeChain>();
+    assert_eq!(change_chain.current(), Some(failed_change));
+    assert!(app
+        .world
+        .get_entity(get_entity_with_remap(test_id_1, &change_chain.entity_remap))
+        .is_some());
+}
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+struct TestResource {
+    value: i32,
+}
+
+#[test]
+fn test_resource_undo() {
+    let mut app = configure_app();
+    app.init_resource::<TestResource>();
+    app.auto_undo_resource::<TestResource>();
+    repeat_update(&mut app, 2);
+
+    app.world.resource_mut::<TestResource>().value = 1;
+    repeat_update(&mut app, 10);
+    app.world.resource_mut::<TestResource>().value = 2;
+    repeat_update(&mut app, 10);
+    //Change detection without real change
+    app.world.resource_mut::<TestResource>().set_changed();
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 2);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<TestResource>().value, 1);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 1);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<TestResource>().value, 0);
+
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<TestResource>().value, 1);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 1);
+    assert_eq!(
+        app.world.resource::<ChangeChain>().changes_for_redo.len(),
+        1
+    );
+}
+
+#[test]
+fn test_resource_registered_twice() {
+    let mut app = configure_app();
+    app.init_resource::<TestResource>();
+    app.auto_undo_resource::<TestResource>();
+    repeat_update(&mut app, 2);
+    app.auto_undo_resource::<TestResource>();
+    repeat_update(&mut app, 2);
+
+    app.world.resource_mut::<TestResource>().value = 1;
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 1);
+    assert!(app
+        .world
+        .resource::<AutoUndoResources>()
+        .contains(std::any::TypeId::of::<TestResource>()));
+}
+
+#[test]
+fn test_ignored_resource_change() {
+    let mut app = configure_app();
+    app.init_resource::<TestResource>();
+    app.auto_undo_resource::<TestResource>();
+    repeat_update(&mut app, 2);
+
+    app.world.resource_mut::<TestResource>().value = 1;
+    app.world
+        .resource_mut::<UndoIgnoreResources>()
+        .types
+        .insert(std::any::TypeId::of::<TestResource>());
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 0);
+    assert!(app.world.resource::<UndoIgnoreResources>().types.is_empty());
+
+    //Next changes are undoable again and start from the ignored value
+    app.world.resource_mut::<TestResource>().value = 2;
+    repeat_update(&mut app, 10);
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<TestResource>().value, 1);
+}
+
+#[derive(Component, Reflect, Default, Clone)]
+#[reflect(Component)]
+struct TestValue(i32);
+
+fn configure_persistent_app() -> App {
+    let mut app = configure_app();
+    app.add_plugins(HierarchyPlugin);
+    app.register_type::<TestValue>();
+    app.auto_reflected_undo::<TestValue>();
+    app
+}
+
+#[test]
+fn test_persistent_history() {
+    let mut app = configure_persistent_app();
+    let test_id = app.world.spawn((UndoMarker, TestValue(0))).id();
+    let removed_id = app.world.spawn((UndoMarker, TestValue(5))).id();
+    repeat_update(&mut app, 12);
+
+    app.world.get_mut::<TestValue>(test_id).unwrap().0 = 1;
+    repeat_update(&mut app, 10);
+    app.world.get_mut::<TestValue>(test_id).unwrap().0 = 2;
+    repeat_update(&mut app, 10);
+    app.world.run_system_once(move |mut commands: Commands| {
+        commands.entity(removed_id).despawn_recursive_with_undo();
+    });
+    repeat_update(&mut app, 10);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 4);
+
+    //Scene file stores entities by its own ids, removed entity is not in the scene
+    let file_id = Entity::from_raw(0);
+    let scene_ids = EntityHashMap::from_iter([(test_id, file_id)]);
+    let history = persistent_history::store_history(&app.world, &scene_ids);
+    assert_eq!(history.scene_entities, vec![file_id.to_bits()]);
+    let data = {
+        let registry = app.world.resource::<AppTypeRegistry>().read();
+        space_persistence::serialize_reflect(&history, &registry).unwrap()
+    };
+
+    //Scene is loaded in new session with different entity ids
+    let mut app = configure_persistent_app();
+    app.world.spawn_empty();
+    let loaded_id = app.world.spawn((UndoMarker, TestValue(2))).id();
+    assert_ne!(loaded_id, test_id);
+    repeat_update(&mut app, 2);
+
+    let history = {
+        let registry = app.world.resource::<AppTypeRegistry>().read();
+        let value = space_persistence::deserialize_reflect(&data, &registry).unwrap();
+        persistent_history::StoredHistory::from_reflect(value.as_ref()).unwrap()
+    };
+    let scene_map = EntityHashMap::from_iter([(file_id, loaded_id)]);
+    persistent_history::restore_history(&mut app.world, &history, &scene_map).unwrap();
+    repeat_update(&mut app, 12);
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 4);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 2);
+    let mut query = app.world.query::<&TestValue>();
+    assert_eq!(query.iter(&app.world).len(), 2);
+    assert!(query.iter(&app.world).any(|value| value.0 == 5));
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    assert_eq!(app.world.get::<TestValue>(loaded_id).unwrap().0, 1);
+
+    app.world.send_event(UndoRedo::Undo);
+    repeat_update(&mut app, 12);
+    assert_eq!(app.world.get::<TestValue>(loaded_id).unwrap().0, 0);
+
+    app.world.send_event(UndoRedo::Redo);
+    repeat_update(&mut app, 12);
+    assert_eq!(app.world.get::<TestValue>(loaded_id).unwrap().0, 1);
+}
+
+#[test]
+fn test_persistent_history_missing_entity() {
+    let mut app = configure_persistent_app();
+    let test_id = app.world.spawn((UndoMarker, TestValue(0))).id();
+    repeat_update(&mut app, 2);
+    app.world.get_mut::<TestValue>(test_id).unwrap().0 = 1;
+    repeat_update(&mut app, 10);
+
+    let scene_ids = EntityHashMap::from_iter([(test_id, test_id)]);
+    let history = persistent_history::store_history(&app.world, &scene_ids);
+    assert_eq!(history.nodes.len(), 1);
+    let res =
+        persistent_history::restore_history(&mut app.world, &history, &EntityHashMap::default());
+    assert!(res.is_err());
+    assert_eq!(app.world.resource::<ChangeChain>().changes.len(), 1);
+}
+
 #[test]
 fn clear_one_frame_ignores() {
     let spawn = |mut commands: Commands| {
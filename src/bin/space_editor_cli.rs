@@ -0,0 +1,429 @@
+//! Command line tool for batch operations with scene files.
+//! It works without window, so content pipelines can run it in CI
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use bevy::{
+    app::PluginsState,
+    asset::LoadState,
+    gltf::Gltf,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use space_editor_core::{gltf_unpack::EditorUnpackGltf, EditorCore};
+use space_prefab::{
+    dependencies::{
+        read_prefab_dependencies, scene_asset_dependencies, scene_dependencies,
+        PrefabDependencyGraph,
+    },
+    load::is_prefab_variant,
+    plugins::BasePrefabPlugin,
+    save::{write_scene_file, SceneSaved},
+    scene_format::{is_scene_file, SceneFormat},
+    validation::SceneValidator,
+};
+use space_shared::{toast::ToastMessage, EditorEvent, EditorPrefabPath, PrefabMarker};
+
+const USAGE: &str = "\
+Usage: space_editor_cli [--assets <folder>] <command> [arguments]
+
+Commands:
+  validate [path...]             Check scenes against the type registry. Paths are scene files
+                                 or folders relative to the assets folder, default is `scenes`
+  convert <input> <output>       Convert scene file between .scn.ron and .scn.bin formats.
+                                 Paths are relative to the assets folder
+  unpack-gltf <gltf> <prefab>    Unpack gltf file to a prefab with editable entities.
+                                 Paths are relative to the assets folder
+  list-deps <scene>              Print prefab and asset dependencies of the scene and prefabs
+                                 which use it. Path is relative to the assets folder
+
+Options:
+  --assets <folder>              Assets folder, default is `assets`";
+
+/// Time to wait for assets and file writes of headless app
+const FRAME_LIMIT: usize = 6000;
+const FRAME_TIME: Duration = Duration::from_millis(10);
+
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+struct Cli {
+    assets: String,
+    command: Command,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Validate(Vec<String>),
+    Convert { input: String, output: String },
+    UnpackGltf { gltf: String, prefab: String },
+    ListDeps(String),
+    Help,
+}
+
+fn main() -> ExitCode {
+    let cli = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(cli) => cli,
+        Err(err) => return usage_error(&err),
+    };
+    //Asset folder of bevy is relative to the executable, while users expect current directory
+    let assets = std::env::current_dir()
+        .map(|dir| dir.join(&cli.assets))
+        .unwrap_or_else(|_| PathBuf::from(&cli.assets));
+
+    let res = match &cli.command {
+        Command::Validate(paths) => validate(&assets, paths),
+        Command::Convert { input, output } => convert(&assets, input, output),
+        Command::UnpackGltf { gltf, prefab } => unpack_gltf(&assets, gltf, prefab),
+        Command::ListDeps(scene) => list_deps(&assets, scene),
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage_error(err: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", err, USAGE);
+    ExitCode::from(2)
+}
+
+fn parse_args(mut args: Vec<String>) -> Result<Cli, String> {
+    let assets = take_option(&mut args, "--assets")?.unwrap_or_else(|| "assets".to_string());
+    let command = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["validate", paths @ ..] => {
+            Command::Validate(paths.iter().map(|path| path.to_string()).collect())
+        }
+        ["convert", input, output] => Command::Convert {
+            input: input.to_string(),
+            output: output.to_string(),
+        },
+        ["unpack-gltf", gltf, prefab] => Command::UnpackGltf {
+            gltf: gltf.to_string(),
+            prefab: prefab.to_string(),
+        },
+        ["list-deps", scene] => Command::ListDeps(scene.to_string()),
+        ["help" | "--help" | "-h"] => Command::Help,
+        _ => return Err("unknown command or wrong arguments".to_string()),
+    };
+    Ok(Cli { assets, command })
+}
+
+/// Remove option with its value from arguments
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(None);
+    };
+    if index + 1 >= args.len() {
+        return Err(format!("{} needs a value", name));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+/// App with assets, rendering resources and editor core, but without window and GPU
+fn headless_editor_app(assets: &Path) -> App {
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(AssetPlugin {
+                file_path: assets.to_string_lossy().to_string(),
+                ..default()
+            })
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+    );
+    app.add_plugins((BasePrefabPlugin, EditorCore));
+    app.add_event::<ToastMessage>();
+
+    #[cfg(feature = "bevy_xpbd_3d")]
+    app.add_plugins(space_bevy_xpbd_plugin::XpbdPlugin);
+
+    //App is updated manually, so plugins are finished here like in App::run
+    while app.plugins_state() == PluginsState::Adding {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Update app until the check returns value
+fn run_until<T>(
+    app: &mut App,
+    waiting_for: &str,
+    mut check: impl FnMut(&mut World) -> Option<T>,
+) -> Result<T, String> {
+    for _ in 0..FRAME_LIMIT {
+        app.update();
+        if let Some(value) = check(&mut app.world) {
+            return Ok(value);
+        }
+        std::thread::sleep(FRAME_TIME);
+    }
+    Err(format!("timed out waiting for {}", waiting_for))
+}
+
+fn validate(assets: &Path, paths: &[String]) -> Result<(), String> {
+    let paths = if paths.is_empty() {
+        vec!["scenes".to_string()]
+    } else {
+        paths.to_vec()
+    };
+
+    //Validator checks scenes against types of editor app
+    let app = headless_editor_app(assets);
+    let mut validator = SceneValidator::new(assets);
+    validator
+        .app_mut()
+        .insert_resource(app.world.resource::<AppTypeRegistry>().clone());
+    let mut reports = vec![];
+    for path in paths.iter() {
+        if is_scene_file(path) {
+            reports.push(validator.validate_file(path));
+        } else {
+            reports.extend(validator.validate_folder(path));
+        }
+    }
+
+    let mut invalid = 0;
+    for report in reports.iter() {
+        if report.is_valid() {
+            println!("ok      {}", report.path);
+        } else {
+            invalid += 1;
+            println!("failed  {}", report.path);
+            for issue in report.issues.iter() {
+                println!("        {}", issue);
+            }
+        }
+    }
+
+    if invalid > 0 {
+        Err(format!(
+            "{} of {} scenes have issues",
+            invalid,
+            reports.len()
+        ))
+    } else {
+        println!("{} scenes are valid", reports.len());
+        Ok(())
+    }
+}
+
+fn convert(assets: &Path, input: &str, output: &str) -> Result<(), String> {
+    if !is_scene_file(input) || !is_scene_file(output) {
+        return Err("scene files must have .scn.ron or .scn.bin extension".to_string());
+    }
+
+    let app = headless_editor_app(assets);
+    let registry = app.world.resource::<AppTypeRegistry>();
+    let bytes = std::fs::read(assets.join(input)).map_err(|err| format!("{}: {}", input, err))?;
+    let scene = SceneFormat::from_path(input)
+        .deserialize(&bytes, &registry.read())
+        .map_err(|err| format!("{}: {}", input, err))?;
+    let data = SceneFormat::from_path(output).serialize(&scene, registry)?;
+    let file = assets.join(output);
+    if let Some(folder) = file.parent() {
+        std::fs::create_dir_all(folder).map_err(|err| format!("{}: {}", folder.display(), err))?;
+    }
+    write_scene_file(&file.to_string_lossy(), &data)
+        .map_err(|err| format!("{}: {}", output, err))?;
+
+    println!(
+        "Converted {} ({} bytes) to {} ({} bytes)",
+        input,
+        bytes.len(),
+        output,
+        data.len()
+    );
+    Ok(())
+}
+
+fn unpack_gltf(assets: &Path, gltf: &str, prefab: &str) -> Result<(), String> {
+    if !is_scene_file(prefab) {
+        return Err("prefab file must have .scn.ron or .scn.bin extension".to_string());
+    }
+
+    let mut app = headless_editor_app(assets);
+    let handle: Handle<Gltf> = app.world.resource::<AssetServer>().load(gltf.to_string());
+    app.world.send_event(EditorUnpackGltf {
+        path: gltf.to_string(),
+    });
+
+    let mut prefab_query = app.world.query_filtered::<(), With<PrefabMarker>>();
+    run_until(&mut app, "gltf unpack", |world| {
+        let assets = world.resource::<AssetServer>();
+        if assets.get_load_state(&handle) == Some(LoadState::Failed) {
+            return Some(Err(format!("can not load {}", gltf)));
+        }
+        prefab_query.iter(world).next().map(|_| Ok(()))
+    })??;
+    let count = prefab_query.iter(&app.world).count();
+
+    let file = assets.join(prefab);
+    if let Some(folder) = file.parent() {
+        std::fs::create_dir_all(folder).map_err(|err| format!("{}: {}", folder.display(), err))?;
+    }
+    let mut saved_events = app.world.resource::<Events<SceneSaved>>().get_reader();
+    app.world
+        .send_event(EditorEvent::Save(EditorPrefabPath::File(
+            file.to_string_lossy().to_string(),
+        )));
+    run_until(&mut app, "prefab save", |world| {
+        saved_events
+            .read(world.resource::<Events<SceneSaved>>())
+            .next()
+            .map(|event| event.result.clone())
+    })??;
+
+    println!("Unpacked {} entities from {} to {}", count, gltf, prefab);
+    Ok(())
+}
+
+fn list_deps(assets: &Path, path: &str) -> Result<(), String> {
+    let app = headless_editor_app(assets);
+    let registry = app.world.resource::<AppTypeRegistry>().read();
+    let bytes = std::fs::read(assets.join(path)).map_err(|err| format!("{}: {}", path, err))?;
+
+    let (prefabs, asset_files) = if is_prefab_variant(path) {
+        let base = read_prefab_dependencies(path, &bytes, &registry)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        (base, vec![])
+    } else {
+        let scene = SceneFormat::from_path(path)
+            .deserialize(&bytes, &registry)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        (scene_dependencies(&scene), scene_asset_dependencies(&scene))
+    };
+
+    let mut graph = PrefabDependencyGraph::default();
+    for err in graph.scan_folder(assets, &registry) {
+        eprintln!("warning: {}", err);
+    }
+
+    print_list("Prefabs", &prefabs);
+    print_list("Assets", &asset_files);
+    print_list("Used by", &graph.users(path));
+    Ok(())
+}
+
+fn print_list(title: &str, items: &[String]) {
+    println!("{}:", title);
+    if items.is_empty() {
+        println!("  (none)");
+    }
+    for item in items {
+        println!("  {}", item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn take_option_removes_option_and_value() {
+        let mut list = args(&["validate", "--assets", "data", "scenes"]);
+
+        assert_eq!(
+            take_option(&mut list, "--assets"),
+            Ok(Some("data".to_string()))
+        );
+        assert_eq!(list, args(&["validate", "scenes"]));
+        assert_eq!(take_option(&mut list, "--assets"), Ok(None));
+    }
+
+    #[test]
+    fn take_option_without_value_is_error() {
+        let mut list = args(&["validate", "--assets"]);
+
+        assert!(take_option(&mut list, "--assets").is_err());
+    }
+
+    #[test]
+    fn arguments_are_dispatched_to_commands() {
+        let parse = |list: &[&str]| parse_args(args(list)).map(|cli| cli.command);
+
+        assert_eq!(parse(&["validate"]), Ok(Command::Validate(vec![])));
+        assert_eq!(
+            parse(&["validate", "a.scn.ron", "levels"]),
+            Ok(Command::Validate(args(&["a.scn.ron", "levels"])))
+        );
+        assert_eq!(
+            parse(&["convert", "a.scn.ron", "a.scn.bin"]),
+            Ok(Command::Convert {
+                input: "a.scn.ron".to_string(),
+                output: "a.scn.bin".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["unpack-gltf", "cube.glb", "cube.scn.ron"]),
+            Ok(Command::UnpackGltf {
+                gltf: "cube.glb".to_string(),
+                prefab: "cube.scn.ron".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["list-deps", "a.scn.ron"]),
+            Ok(Command::ListDeps("a.scn.ron".to_string()))
+        );
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+        assert!(parse(&["convert", "a.scn.ron"]).is_err());
+        assert!(parse(&["list-deps"]).is_err());
+        assert!(parse(&["build"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn assets_option_can_be_anywhere() {
+        assert_eq!(
+            parse_args(args(&["list-deps", "a.scn.ron"]))
+                .unwrap()
+                .assets,
+            "assets"
+        );
+        assert_eq!(
+            parse_args(args(&["list-deps", "a.scn.ron", "--assets", "data"])),
+            Ok(Cli {
+                assets: "data".to_string(),
+                command: Command::ListDeps("a.scn.ron".to_string())
+            })
+        );
+    }
+}